@@ -0,0 +1,141 @@
+use regex::Regex;
+
+use crate::config::{AlertConfig, AlertLevel};
+
+#[derive(Clone, Debug)]
+pub struct AlertRule {
+    pub regex: Regex,
+    pub level: AlertLevel,
+}
+
+pub fn compile_alert_rules(label: &str, alerts: &Option<Vec<AlertConfig>>) -> Vec<AlertRule> {
+    alerts
+        .as_ref()
+        .unwrap_or(&vec![])
+        .iter()
+        .filter_map(|alert| match Regex::new(&alert.pattern) {
+            Ok(regex) => Some(AlertRule {
+                regex,
+                level: alert.level,
+            }),
+            Err(e) => {
+                error!(
+                    "Invalid alert pattern for process {} ({}): {}",
+                    label, alert.pattern, e
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn compile_ready_rule(label: &str, ready_pattern: &Option<String>) -> Option<Regex> {
+    let pattern = ready_pattern.as_ref()?;
+    match Regex::new(pattern) {
+        Ok(regex) => Some(regex),
+        Err(e) => {
+            error!(
+                "Invalid ready pattern for process {} ({}): {}",
+                label, pattern, e
+            );
+            None
+        }
+    }
+}
+
+/*
+ * Returns the most severe level of all rules matching the line, if any
+ */
+pub fn match_alert_rules(rules: &[AlertRule], line: &str) -> Option<AlertLevel> {
+    rules
+        .iter()
+        .filter(|rule| rule.regex.is_match(line))
+        .map(|rule| rule.level)
+        .max()
+}
+
+/*
+ * Removes terminal escape sequences so alert patterns can be
+ * written against the text a user actually sees
+ */
+pub fn strip_ansi(s: &str) -> String {
+    let mut stripped = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            if !c.is_control() || c == '\t' {
+                stripped.push(c);
+            }
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters/intermediates until a final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by BEL or ST (ESC \)
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_ansi_removes_color_codes() {
+        assert_eq!(
+            strip_ansi("\x1b[1;31merror\x1b[0m: oops\r"),
+            "error: oops".to_string()
+        );
+        assert_eq!(strip_ansi("\x1b]0;title\x07plain"), "plain".to_string());
+    }
+
+    #[test]
+    fn match_alert_rules_picks_most_severe_level() {
+        let rules = compile_alert_rules(
+            "test",
+            &Some(vec![
+                AlertConfig {
+                    pattern: "warn".to_string(),
+                    level: AlertLevel::Warn,
+                },
+                AlertConfig {
+                    pattern: "^error".to_string(),
+                    level: AlertLevel::Error,
+                },
+                AlertConfig {
+                    pattern: "(".to_string(),
+                    level: AlertLevel::Info,
+                },
+            ]),
+        );
+        assert_eq!(rules.len(), 2);
+        assert_eq!(
+            match_alert_rules(&rules, "error: warn"),
+            Some(AlertLevel::Error)
+        );
+        assert_eq!(
+            match_alert_rules(&rules, "a warning"),
+            Some(AlertLevel::Warn)
+        );
+        assert_eq!(match_alert_rules(&rules, "all good"), None);
+    }
+}
@@ -1,14 +1,57 @@
-use std::{env, error::Error, fs};
+use std::{env, error::Error, fs, path::Path};
 
 use crate::config::ProcTmuxConfig;
 
 pub fn parse_config_from_args() -> Result<ProcTmuxConfig, Box<dyn Error>> {
-    let args: Vec<String> = env::args().collect();
     let mut config_file = "proctmux.yaml".to_string();
-    if args.len() >= 2 {
-        config_file = args[1].to_string();
+    let mut profile = None;
+    let mut tmux_socket = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-p" | "--profile" => match args.next() {
+                Some(p) => profile = Some(p),
+                None => return Err(Box::from(format!("Missing value for {}", arg))),
+            },
+            "-L" | "-S" => match args.next() {
+                Some(s) if arg == "-S" && !s.contains('/') => {
+                    tmux_socket = Some(format!("./{}", s))
+                }
+                Some(s) => tmux_socket = Some(s),
+                None => return Err(Box::from(format!("Missing value for {}", arg))),
+            },
+            _ => config_file = arg,
+        }
+    }
+    let mut proctmux_config: ProcTmuxConfig =
+        serde_yaml::from_reader(fs::File::open(&config_file).unwrap())?;
+    if proctmux_config.general.order_file.is_none() {
+        proctmux_config.general.order_file = Some(default_order_file(&config_file));
+    }
+    if tmux_socket.is_some() {
+        proctmux_config.general.tmux_socket = tmux_socket;
+    }
+    if profile.is_some() {
+        proctmux_config.general.profile = profile;
+    }
+    if let Some(profile) = &proctmux_config.general.profile {
+        if !proctmux_config.groups.contains_key(profile) {
+            return Err(Box::from(format!("Unknown profile (group): {}", profile)));
+        }
     }
-    let config_file = fs::File::open(config_file).unwrap();
-    let proctmux_config: ProcTmuxConfig = serde_yaml::from_reader(config_file)?;
     Ok(proctmux_config)
 }
+
+/*
+ * Process list order is persisted in a hidden file beside the config file
+ */
+fn default_order_file(config_file: &str) -> String {
+    let path = Path::new(config_file);
+    let file_name = path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or("proctmux.yaml".to_string());
+    path.with_file_name(format!(".{}.order", file_name))
+        .to_string_lossy()
+        .to_string()
+}
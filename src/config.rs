@@ -1,5 +1,6 @@
 use std::{collections::HashMap, env, ffi::c_int, path::PathBuf};
 
+use indexmap::IndexMap;
 use serde::{Deserialize, Deserializer, Serialize};
 use termion::event::Key;
 
@@ -11,6 +12,12 @@ fn default_general() -> GeneralConfig {
     GeneralConfig {
         detached_session_name: default_detached_session_name(),
         kill_existing_session: default_kill_existing_session(),
+        profile: None,
+        scope_bulk_actions_to_filter: default_scope_bulk_actions_to_filter(),
+        order_file: None,
+        tmux_socket: None,
+        tmux_bin: default_tmux_bin(),
+        control_socket: None,
     }
 }
 
@@ -21,6 +28,34 @@ fn default_layout() -> LayoutConfig {
         process_list_width: default_process_list_width(),
         sort_process_list_alpha: default_sort_process_list_alpha(),
         category_search_prefix: default_category_search_prefix(),
+        group_search_prefix: default_group_search_prefix(),
+        status_search_prefix: default_status_search_prefix(),
+        tag_search_prefix: default_tag_search_prefix(),
+        group_by_category: default_group_by_category(),
+        process_list_columns: vec![],
+        process_pane_size: default_process_pane_size(),
+        split_direction: default_split_direction(),
+        sidebar_position: None,
+        resize_step: default_resize_step(),
+        split_view_layout: default_split_view_layout(),
+    }
+}
+
+fn default_notifications() -> NotificationConfig {
+    NotificationConfig {
+        events: default_notification_events(),
+        tmux_message: default_notification_tmux_message(),
+        bell: default_notification_bell(),
+        command: None,
+    }
+}
+
+fn default_resources() -> ResourceConfig {
+    ResourceConfig {
+        enabled: None,
+        interval_ms: default_resources_interval_ms(),
+        cpu_warning_percent: None,
+        memory_warning_mb: None,
     }
 }
 
@@ -32,7 +67,15 @@ fn default_style() -> StyleConfig {
         status_running_color: default_status_running_color(),
         status_stopped_color: default_status_stopped_color(),
         status_halting_color: default_status_halting_color(),
+        status_success_color: default_status_success_color(),
+        status_failure_color: default_status_failure_color(),
+        marked_process_color: default_marked_process_color(),
+        mark_char: default_mark_char(),
+        alert_error_color: default_alert_error_color(),
+        alert_warn_color: default_alert_warn_color(),
+        alert_info_color: default_alert_info_color(),
         pointer_char: default_pointer_char(),
+        process_column_color: default_process_column_color(),
     }
 }
 
@@ -40,13 +83,36 @@ fn default_style() -> StyleConfig {
 pub struct ProcTmuxConfig {
     #[serde(default = "default_general")]
     pub general: GeneralConfig,
-    pub procs: HashMap<String, ProcessConfig>,
+    pub procs: IndexMap<String, ProcessConfig>,
+    #[serde(default)]
+    pub groups: HashMap<String, Vec<String>>,
     pub keybinding: KeybindingConfig,
     pub log_file: String,
     #[serde(default = "default_layout")]
     pub layout: LayoutConfig,
     #[serde(default = "default_style")]
     pub style: StyleConfig,
+    #[serde(default = "default_notifications")]
+    pub notifications: NotificationConfig,
+    #[serde(default = "default_resources")]
+    pub resources: ResourceConfig,
+}
+
+impl ProcTmuxConfig {
+    /*
+     * resources.enabled takes precedence, otherwise usage is only sampled
+     * when something shows it: a cpu/memory column or a warning threshold
+     */
+    pub fn resources_enabled(&self) -> bool {
+        self.resources.enabled.unwrap_or_else(|| {
+            self.layout
+                .process_list_columns
+                .iter()
+                .any(|c| matches!(c, ProcessColumn::Cpu | ProcessColumn::Memory))
+                || self.resources.cpu_warning_percent.is_some()
+                || self.resources.memory_warning_mb.is_some()
+        })
+    }
 }
 
 fn default_kill_signal() -> c_int {
@@ -65,6 +131,9 @@ fn default_autostart() -> bool {
 fn default_autofocus() -> bool {
     false
 }
+fn default_pinned() -> bool {
+    false
+}
 fn default_quit_keybinding() -> Vec<Key> {
     vec![Key::Char('q')]
 }
@@ -74,6 +143,60 @@ fn default_start_keybinding() -> Vec<Key> {
 fn default_stop_keybinding() -> Vec<Key> {
     vec![Key::Char('x')]
 }
+fn default_restart_keybinding() -> Vec<Key> {
+    vec![Key::Char('r')]
+}
+fn default_start_group_keybinding() -> Vec<Key> {
+    vec![Key::Char('S')]
+}
+fn default_stop_group_keybinding() -> Vec<Key> {
+    vec![Key::Char('X')]
+}
+fn default_restart_group_keybinding() -> Vec<Key> {
+    vec![Key::Char('R')]
+}
+fn default_start_all_keybinding() -> Vec<Key> {
+    vec![Key::Char('a')]
+}
+fn default_stop_all_keybinding() -> Vec<Key> {
+    vec![Key::Char('A')]
+}
+fn default_restart_all_keybinding() -> Vec<Key> {
+    vec![Key::Ctrl('r')]
+}
+fn default_mark_keybinding() -> Vec<Key> {
+    vec![Key::Char('m')]
+}
+fn default_clear_marks_keybinding() -> Vec<Key> {
+    vec![Key::Char('M')]
+}
+fn default_toggle_category_keybinding() -> Vec<Key> {
+    vec![Key::Char('c')]
+}
+fn default_move_up_keybinding() -> Vec<Key> {
+    vec![Key::Char('K')]
+}
+fn default_move_down_keybinding() -> Vec<Key> {
+    vec![Key::Char('J')]
+}
+fn default_toggle_details_keybinding() -> Vec<Key> {
+    vec![Key::Char('i')]
+}
+fn default_grow_pane_keybinding() -> Vec<Key> {
+    vec![Key::Char('>')]
+}
+fn default_shrink_pane_keybinding() -> Vec<Key> {
+    vec![Key::Char('<')]
+}
+fn default_pop_out_keybinding() -> Vec<Key> {
+    vec![Key::Char('o')]
+}
+fn default_popup_keybinding() -> Vec<Key> {
+    vec![Key::Char('p')]
+}
+fn default_toggle_split_view_keybinding() -> Vec<Key> {
+    vec![Key::Char('v')]
+}
 fn default_up_keybinding() -> Vec<Key> {
     vec![Key::Char('k'), Key::Up]
 }
@@ -140,7 +263,7 @@ where
                 }
             }
             if key.len() == 1 {
-                if let Some(c) = key.chars().nth(0) {
+                if let Some(c) = key.chars().next() {
                     return Key::Char(c);
                 }
             }
@@ -166,6 +289,96 @@ pub struct KeybindingConfig {
         deserialize_with = "deserialize_keybinding_notation"
     )]
     pub stop: Vec<Key>,
+    #[serde(
+        default = "default_restart_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub restart: Vec<Key>,
+    #[serde(
+        default = "default_start_group_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub start_group: Vec<Key>,
+    #[serde(
+        default = "default_stop_group_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub stop_group: Vec<Key>,
+    #[serde(
+        default = "default_restart_group_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub restart_group: Vec<Key>,
+    #[serde(
+        default = "default_start_all_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub start_all: Vec<Key>,
+    #[serde(
+        default = "default_stop_all_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub stop_all: Vec<Key>,
+    #[serde(
+        default = "default_restart_all_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub restart_all: Vec<Key>,
+    #[serde(
+        default = "default_mark_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub mark: Vec<Key>,
+    #[serde(
+        default = "default_clear_marks_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub clear_marks: Vec<Key>,
+    #[serde(
+        default = "default_toggle_category_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub toggle_category: Vec<Key>,
+    #[serde(
+        default = "default_move_up_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub move_up: Vec<Key>,
+    #[serde(
+        default = "default_move_down_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub move_down: Vec<Key>,
+    #[serde(
+        default = "default_toggle_details_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub toggle_details: Vec<Key>,
+    #[serde(
+        default = "default_grow_pane_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub grow_pane: Vec<Key>,
+    #[serde(
+        default = "default_shrink_pane_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub shrink_pane: Vec<Key>,
+    #[serde(
+        default = "default_toggle_split_view_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub toggle_split_view: Vec<Key>,
+    #[serde(
+        default = "default_pop_out_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub pop_out: Vec<Key>,
+    #[serde(
+        default = "default_popup_keybinding",
+        deserialize_with = "deserialize_keybinding_notation"
+    )]
+    pub popup: Vec<Key>,
     #[serde(
         default = "default_up_keybinding",
         deserialize_with = "deserialize_keybinding_notation"
@@ -193,12 +406,28 @@ pub struct KeybindingConfig {
     pub switch_focus: Vec<Key>,
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessKind {
+    Service,
+    Task,
+}
+
+fn default_process_kind() -> ProcessKind {
+    ProcessKind::Service
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Eq)]
 pub struct ProcessConfig {
+    #[serde(default = "default_process_kind")]
+    pub kind: ProcessKind,
     #[serde(default = "default_autostart")]
     pub autostart: bool,
     #[serde(default = "default_autofocus")]
     pub autofocus: bool,
+    #[serde(default = "default_pinned")]
+    pub pinned: bool,
+    pub order: Option<i64>,
     pub shell: Option<String>,
     pub cmd: Option<Vec<String>>,
     #[serde(default = "current_working_dir")]
@@ -214,6 +443,31 @@ pub struct ProcessConfig {
     pub docs: Option<String>,
     pub categories: Option<Vec<String>>,
     pub meta_tags: Option<Vec<String>>,
+    pub alerts: Option<Vec<AlertConfig>>,
+    pub ready_pattern: Option<String>,
+    pub before_start: Option<String>,
+    pub after_start: Option<String>,
+    pub after_stop: Option<String>,
+    pub on_fail: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+fn default_alert_level() -> AlertLevel {
+    AlertLevel::Error
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Eq)]
+pub struct AlertConfig {
+    pub pattern: String,
+    #[serde(default = "default_alert_level")]
+    pub level: AlertLevel,
 }
 
 fn default_detached_session_name() -> String {
@@ -224,12 +478,30 @@ fn default_kill_existing_session() -> bool {
     false
 }
 
+fn default_scope_bulk_actions_to_filter() -> bool {
+    true
+}
+
+fn default_tmux_bin() -> String {
+    "tmux".to_string()
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Eq)]
 pub struct GeneralConfig {
     #[serde(default = "default_detached_session_name")]
     pub detached_session_name: String,
     #[serde(default = "default_kill_existing_session")]
     pub kill_existing_session: bool,
+    pub profile: Option<String>,
+    #[serde(default = "default_scope_bulk_actions_to_filter")]
+    pub scope_bulk_actions_to_filter: bool,
+    pub order_file: Option<String>,
+    // socket name (tmux -L), or socket path (tmux -S) when it contains a '/'
+    pub tmux_socket: Option<String>,
+    #[serde(default = "default_tmux_bin")]
+    pub tmux_bin: String,
+    // path of the unix socket accepting control API commands (see control_api)
+    pub control_socket: Option<String>,
 }
 
 fn default_hide_help() -> bool {
@@ -252,6 +524,66 @@ fn default_category_search_prefix() -> String {
     "cat:".to_string()
 }
 
+fn default_group_search_prefix() -> String {
+    "group:".to_string()
+}
+
+fn default_status_search_prefix() -> String {
+    "status:".to_string()
+}
+
+fn default_tag_search_prefix() -> String {
+    "tag:".to_string()
+}
+
+fn default_group_by_category() -> bool {
+    false
+}
+
+fn default_process_pane_size() -> String {
+    "70%".to_string()
+}
+
+fn default_split_direction() -> SplitDirection {
+    SplitDirection::Horizontal
+}
+
+fn default_resize_step() -> u16 {
+    5
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitDirection {
+    Horizontal,
+    Vertical,
+}
+
+fn default_split_view_layout() -> SplitViewLayout {
+    SplitViewLayout::Tiled
+}
+
+/*
+ * How the panes of the split view are arranged beside the sidebar,
+ * named after the equivalent tmux layouts
+ */
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SplitViewLayout {
+    Tiled,
+    EvenVertical,
+    EvenHorizontal,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SidebarPosition {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
 // fn default_field_replacement_prompt() -> String {
 //     "__FIELD_NAME__ ⮕  ".to_string()
 // }
@@ -267,10 +599,105 @@ pub struct LayoutConfig {
     pub sort_process_list_alpha: bool,
     #[serde(default = "default_category_search_prefix")]
     pub category_search_prefix: String,
+    #[serde(default = "default_group_search_prefix")]
+    pub group_search_prefix: String,
+    #[serde(default = "default_status_search_prefix")]
+    pub status_search_prefix: String,
+    #[serde(default = "default_tag_search_prefix")]
+    pub tag_search_prefix: String,
+    #[serde(default = "default_group_by_category")]
+    pub group_by_category: bool,
+    #[serde(default)]
+    pub process_list_columns: Vec<ProcessColumn>,
+    #[serde(default = "default_process_pane_size")]
+    pub process_pane_size: String,
+    #[serde(default = "default_split_direction")]
+    pub split_direction: SplitDirection,
+    pub sidebar_position: Option<SidebarPosition>,
+    #[serde(default = "default_resize_step")]
+    pub resize_step: u16,
+    #[serde(default = "default_split_view_layout")]
+    pub split_view_layout: SplitViewLayout,
     // #[serde(default = "default_field_replacement_prompt")]
     // field_replacement_prompt: str = '__FIELD_NAME__ ⮕  '
 }
 
+impl LayoutConfig {
+    /*
+     * sidebar_position takes precedence, otherwise the sidebar is
+     * left of (horizontal) or above (vertical) the process pane
+     */
+    pub fn sidebar_position(&self) -> SidebarPosition {
+        self.sidebar_position.unwrap_or(match self.split_direction {
+            SplitDirection::Horizontal => SidebarPosition::Left,
+            SplitDirection::Vertical => SidebarPosition::Top,
+        })
+    }
+}
+
+/*
+ * Extra information shown right-aligned after each process name
+ */
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessColumn {
+    Uptime,
+    Restarts,
+    Pid,
+    ExitCode,
+    Cpu,
+    Memory,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationEvent {
+    Exit,
+    Fail,
+    Ready,
+    Alert,
+    Usage,
+}
+
+fn default_notification_events() -> Vec<NotificationEvent> {
+    vec![NotificationEvent::Fail]
+}
+
+fn default_notification_tmux_message() -> bool {
+    true
+}
+
+fn default_notification_bell() -> bool {
+    false
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Eq)]
+pub struct NotificationConfig {
+    #[serde(default = "default_notification_events")]
+    pub events: Vec<NotificationEvent>,
+    #[serde(default = "default_notification_tmux_message")]
+    pub tmux_message: bool,
+    #[serde(default = "default_notification_bell")]
+    pub bell: bool,
+    pub command: Option<String>,
+}
+
+fn default_resources_interval_ms() -> u64 {
+    2000
+}
+
+/*
+ * Periodic sampling of the CPU and memory usage of running processes
+ */
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Eq)]
+pub struct ResourceConfig {
+    pub enabled: Option<bool>,
+    #[serde(default = "default_resources_interval_ms")]
+    pub interval_ms: u64,
+    pub cpu_warning_percent: Option<u32>,
+    pub memory_warning_mb: Option<u64>,
+}
+
 fn default_selected_process_color() -> String {
     "ansiblack".to_string()
 }
@@ -294,10 +721,42 @@ fn default_status_stopped_color() -> String {
 fn default_status_halting_color() -> String {
     "ansiyellow".to_string()
 }
+fn default_status_success_color() -> String {
+    "ansigreen".to_string()
+}
+
+fn default_status_failure_color() -> String {
+    "ansired".to_string()
+}
+
+fn default_marked_process_color() -> String {
+    "ansiyellow".to_string()
+}
+
+fn default_mark_char() -> String {
+    "*".to_string()
+}
+
+fn default_alert_error_color() -> String {
+    "ansired".to_string()
+}
+
+fn default_alert_warn_color() -> String {
+    "ansiyellow".to_string()
+}
+
+fn default_alert_info_color() -> String {
+    "ansicyan".to_string()
+}
+
 fn default_pointer_char() -> String {
     "▶".to_string()
 }
 
+fn default_process_column_color() -> String {
+    "ansilightblack".to_string()
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Eq)]
 pub struct StyleConfig {
     #[serde(default = "default_selected_process_color")]
@@ -313,9 +772,25 @@ pub struct StyleConfig {
     // pub placeholder_terminal_bg_color: String,
     #[serde(default = "default_status_halting_color")]
     pub status_halting_color: String,
+    #[serde(default = "default_status_success_color")]
+    pub status_success_color: String,
+    #[serde(default = "default_status_failure_color")]
+    pub status_failure_color: String,
+    #[serde(default = "default_marked_process_color")]
+    pub marked_process_color: String,
+    #[serde(default = "default_mark_char")]
+    pub mark_char: String,
+    #[serde(default = "default_alert_error_color")]
+    pub alert_error_color: String,
+    #[serde(default = "default_alert_warn_color")]
+    pub alert_warn_color: String,
+    #[serde(default = "default_alert_info_color")]
+    pub alert_info_color: String,
 
     #[serde(default = "default_pointer_char")]
     pub pointer_char: String,
+    #[serde(default = "default_process_column_color")]
+    pub process_column_color: String,
 }
 
 #[cfg(test)]
@@ -1,4 +1,12 @@
 pub const UP: char = '▲';
 pub const DOWN: char = '▼';
+pub const SUCCESS: char = '✔';
+pub const FAILURE: char = '✘';
+pub const ALERT_BADGE: char = '●';
+pub const RESTARTS: char = '↻';
+pub const EXPANDED: char = '▾';
+pub const COLLAPSED: char = '▸';
+pub static CATEGORY_INDENT: &str = "  ";
 pub static ANSI_PREFIX: &str = "ansi";
 pub static MIN_SCREEN_HEIGHT: u16 = 10;
+pub static STATUS_HISTORY_LENGTH: usize = 10;
@@ -0,0 +1,116 @@
+/*
+    A control API for scripting proctmux: a unix socket accepting one command
+    per line, each answered with "ok" or "error: <reason>", e.g.
+    echo start_all | nc -U /tmp/proctmux.sock
+*/
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+
+use crate::controller::Controller;
+
+// each acting on every process, unlike the start_all/stop_all/restart_all
+// keybindings, which may only act on the processes the filter shows
+#[derive(Debug, PartialEq)]
+enum Command {
+    Start,
+    Stop,
+    Restart,
+}
+
+impl Command {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "start_all" => Some(Command::Start),
+            "stop_all" => Some(Command::Stop),
+            "restart_all" => Some(Command::Restart),
+            _ => None,
+        }
+    }
+
+    fn run(&self, controller: &Controller) -> Result<(), Box<dyn Error>> {
+        match self {
+            Command::Start => controller.on_control_start_all(),
+            Command::Stop => controller.on_control_stop_all(),
+            Command::Restart => controller.on_control_restart_all(),
+        }
+    }
+}
+
+pub fn listen(path: &str, controller: Arc<Mutex<Controller>>) -> Result<(), Box<dyn Error>> {
+    // left behind by a proctmux which did not exit cleanly
+    if let Ok(metadata) = fs::metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(Box::from(format!(
+                "Control socket {} exists and is not a socket",
+                path
+            )));
+        }
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)
+        .map_err(|e| format!("Could not create control socket {}: {}", path, e))?;
+    info!("Listening for control API commands on {}", path);
+
+    spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let controller = controller.clone();
+                    spawn(move || handle_connection(stream, controller));
+                }
+                Err(e) => error!("Error accepting control API connection: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle_connection(stream: UnixStream, controller: Arc<Mutex<Controller>>) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            error!("Error handling control API connection: {}", e);
+            return;
+        }
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        let text = line.trim();
+        if text.is_empty() {
+            continue;
+        }
+        info!("Control API command: {}", text);
+        let result = match Command::parse(text) {
+            Some(command) => command.run(&controller.lock().unwrap()),
+            None => Err(Box::from(format!("unknown command: {}", text))),
+        };
+        let response = match result {
+            Ok(_) => "ok".to_string(),
+            Err(e) => format!("error: {}", e),
+        };
+        if writeln!(writer, "{}", response).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::parse("start_all"), Some(Command::Start));
+        assert_eq!(Command::parse("stop_all"), Some(Command::Stop));
+        assert_eq!(Command::parse("restart_all"), Some(Command::Restart));
+        assert_eq!(Command::parse("start all"), None);
+    }
+}
@@ -1,20 +1,43 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
 use std::io::Stdout;
+use std::process::Child;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use termion::raw::RawTerminal;
 
+use crate::alert::match_alert_rules;
+use crate::config::{NotificationConfig, NotificationEvent};
 use crate::draw::{draw_screen, init_screen, prepare_screen_for_exit};
 use crate::gui_state::GUIStateMutation;
+use crate::hooks::{Hook, HookRunner};
+use crate::notification::notify;
+use crate::order::save_order;
 use crate::process::{Process, ProcessStatus};
+use crate::resources::{exceeds_threshold, format_bytes, ResourceUsage};
 use crate::state::{Mutator, State, StateMutation};
-use crate::tmux;
+use crate::tmux_backend::{DeadPane, PaneNotFound, PaneState};
 use crate::tmux_context::TmuxContext;
 
+/*
+ * A notification to send once the state is unlocked (see send_notifications)
+ */
+struct PendingNotification {
+    config: NotificationConfig,
+    event: NotificationEvent,
+    process: Process,
+    message: String,
+}
+
+type PendingNotifications = RefCell<Vec<PendingNotification>>;
+
 pub struct Controller {
     state: Mutex<State>,
     tmux_context: TmuxContext,
+    hooks: HookRunner,
     stdout: RawTerminal<Stdout>,
     running: Arc<AtomicBool>,
 }
@@ -23,11 +46,13 @@ impl Controller {
     pub fn new(
         state: State,
         tmux_context: TmuxContext,
+        hooks: HookRunner,
         running: Arc<AtomicBool>,
     ) -> Result<Self, Box<dyn Error>> {
         Ok(Controller {
             state: Mutex::new(state),
             tmux_context,
+            hooks,
             stdout: init_screen()?,
             running,
         })
@@ -49,9 +74,7 @@ impl Controller {
                     self.draw_screen(&state)
                 }
                 Ok(None) => Ok(()),
-                Err(e) => {
-                    return Err(e);
-                }
+                Err(e) => Err(e),
             },
             Err(e) => {
                 error!("lock_and_load => Failed to lock state: {}", e);
@@ -112,14 +135,7 @@ impl Controller {
 
     pub fn on_error(&self, err: Box<dyn Error>) -> Result<(), Box<dyn Error>> {
         trace!("on_error");
-        self.lock_and_load(|state| {
-            let gui_state = GUIStateMutation::on(&state.gui_state)
-                .add_message(format!("{}", err))
-                .commit();
-            Ok(Some(
-                StateMutation::on(state).set_gui_state(gui_state).commit(),
-            ))
-        })
+        self.lock_and_load(|state| Ok(Some(add_message(state, format!("{}", err)))))
     }
 
     fn draw_screen(&self, state: &State) -> Result<(), Box<dyn Error>> {
@@ -133,8 +149,8 @@ impl Controller {
         self.lock_and_load(|state| {
             let mut new_state = state.clone();
             for process in &state.processes {
-                if process.config.autostart {
-                    match start_process(&new_state, &self.tmux_context, &process) {
+                if state.is_autostart(process) {
+                    match start_process(&new_state, &self.tmux_context, &self.hooks, process) {
                         Ok(Some(s)) => new_state = s,
                         Ok(None) => {}
                         Err(e) => error!("Error auto-starting process {}: {}", process.label, e),
@@ -147,27 +163,9 @@ impl Controller {
 
     pub fn on_exit(&self) {
         trace!("on_exit");
-        if let Err(e) = self.lock_and_load(|state| {
-            Ok(Some(
-                state
-                    .processes
-                    .iter()
-                    .filter(|process| process.status == ProcessStatus::Halted)
-                    .fold(state.clone(), |acc, process| {
-                        match kill_pane(&acc, process) {
-                            Ok(Some(s)) => s,
-                            Ok(None) => acc,
-                            Err(e) => {
-                                error!(
-                                    "Error killing pane for process {} in on_exit: {}",
-                                    process.label, e
-                                );
-                                acc
-                            }
-                        }
-                    }),
-            ))
-        }) {
+        if let Err(e) =
+            self.lock_and_load(|state| Ok(Some(kill_halted_panes(state, &self.tmux_context))))
+        {
             error!("Error killing panes in on_exit: {}", e);
         }
 
@@ -178,6 +176,9 @@ impl Controller {
         if let Err(e) = prepare_screen_for_exit(&self.stdout) {
             error!("Error preparing screen for exit in on_exit: {}", e);
         }
+
+        // e.g. the after_stop hooks of the processes halted while quitting
+        self.hooks.wait();
     }
 
     pub fn on_keypress_quit(&self) -> Result<(), Box<dyn Error>> {
@@ -186,152 +187,835 @@ impl Controller {
             if state.exiting {
                 return Ok(None);
             }
-            let new_state = StateMutation::on(state).set_exiting().commit();
-            Ok(Some(
-                new_state
-                    .processes
-                    .iter()
-                    .filter(|process| process.status != ProcessStatus::Halted)
-                    .fold(new_state.clone(), |acc, process| {
-                        match halt_process(&acc, Some(process)) {
-                            Ok(Some(s)) => s,
-                            Ok(None) => acc,
-                            Err(e) => {
-                                error!(
-                                    "Error halting process {} in on_keypress_quit: {}",
-                                    process.label, e
-                                );
-                                acc
-                            }
-                        }
-                    }),
-            ))
+            Ok(Some(halt_all_and_exit(state)))
         })
     }
 
     pub fn on_keypress_down(&self) -> Result<(), Box<dyn Error>> {
         trace!("on_keypress_down");
+        self.lock_and_load(|state| Ok(Some(move_selection(state, &self.tmux_context, 1)?)))
+    }
+
+    pub fn on_keypress_up(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_up");
+        self.lock_and_load(|state| Ok(Some(move_selection(state, &self.tmux_context, -1)?)))
+    }
+
+    pub fn on_keypress_toggle_category(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_toggle_category");
         self.lock_and_load(|state| {
-            break_pane(state, &self.tmux_context, state.current_proc_id)?;
-            let new_state = StateMutation::on(state).next_process().commit();
-            match join_pane(&new_state, &self.tmux_context, new_state.current_proc_id) {
-                Ok(_) => Ok(Some(new_state)),
-                Err(e) => {
-                    error!(
-                        "Error joining pane (proc id: {}): {}",
-                        new_state.current_proc_id, e
-                    );
-                    Ok(Some(new_state))
-                }
+            if !state.config.layout.group_by_category {
+                return Ok(None);
+            }
+            let new_state = StateMutation::on(state).toggle_current_category().commit();
+            if new_state.current_proc_id != state.current_proc_id {
+                break_pane(state, &self.tmux_context, state.current_proc_id)?;
+                mark_selected_pane(&new_state, &self.tmux_context)?;
             }
+            Ok(Some(new_state))
         })
     }
 
-    pub fn on_keypress_up(&self) -> Result<(), Box<dyn Error>> {
-        trace!("on_keypress_up");
+    pub fn on_keypress_toggle_details(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_toggle_details");
+        self.lock_and_load(|state| {
+            let gui_state = GUIStateMutation::on(&state.gui_state)
+                .toggle_details()
+                .commit();
+            Ok(Some(
+                StateMutation::on(state).set_gui_state(gui_state).commit(),
+            ))
+        })
+    }
+
+    /*
+     * Shows the panes of all marked processes side by side, or
+     * returns to showing only the pane of the selected process
+     */
+    pub fn on_keypress_toggle_split_view(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_toggle_split_view");
         self.lock_and_load(|state| {
+            if !state.gui_state.split_view_proc_ids.is_empty() {
+                for process in split_view_processes(state) {
+                    if let Some(pane_id) = &process.pane_id {
+                        ignore_missing_pane(self.tmux_context.break_pane(
+                            pane_id,
+                            process.id,
+                            &process.label,
+                        ))?;
+                    }
+                }
+                self.tmux_context.mark_pane(None)?;
+                let gui_state = GUIStateMutation::on(&state.gui_state)
+                    .set_split_view_proc_ids(vec![])
+                    .commit();
+                let new_state = StateMutation::on(state).set_gui_state(gui_state).commit();
+                join_pane(&new_state, &self.tmux_context, new_state.current_proc_id)?;
+                return Ok(Some(new_state));
+            }
+
+            let process_ids: Vec<usize> = state
+                .processes
+                .iter()
+                .filter(|p| state.gui_state.marked_proc_ids.contains(&p.id))
+                .map(|p| p.id)
+                .collect();
+            if process_ids.is_empty() {
+                return Ok(Some(add_message(
+                    state,
+                    "Mark processes to show them side by side".to_string(),
+                )));
+            }
             break_pane(state, &self.tmux_context, state.current_proc_id)?;
-            let new_state = StateMutation::on(state).previous_process().commit();
-            match join_pane(&new_state, &self.tmux_context, new_state.current_proc_id) {
-                Ok(_) => Ok(Some(new_state)),
-                Err(e) => {
-                    error!(
-                        "Error joining pane (proc id: {}): {}",
-                        new_state.current_proc_id, e
-                    );
-                    Ok(Some(new_state))
+            let gui_state = GUIStateMutation::on(&state.gui_state)
+                .set_split_view_proc_ids(process_ids)
+                .commit();
+            let new_state = StateMutation::on(state).set_gui_state(gui_state).commit();
+            layout_split_view(&new_state, &self.tmux_context)?;
+            Ok(Some(new_state))
+        })
+    }
+
+    pub fn on_keypress_pop_out(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_pop_out");
+        self.lock_and_load(|state| toggle_pop_out(state, &self.tmux_context))
+    }
+
+    /*
+     * Returns the popup, on_popup_closed should be called once it exits
+     */
+    pub fn on_keypress_popup(&self) -> Result<Option<(usize, Child)>, Box<dyn Error>> {
+        trace!("on_keypress_popup");
+        let popup = RefCell::new(None);
+        self.lock_and_load(|state| open_popup(state, &self.tmux_context, &popup))?;
+        Ok(popup.into_inner())
+    }
+
+    pub fn on_popup_closed(&self, process_id: usize) -> Result<(), Box<dyn Error>> {
+        trace!("on_popup_closed");
+        self.lock_and_load(|state| close_popup(state, &self.tmux_context, process_id))
+    }
+
+    pub fn on_keypress_grow_pane(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_grow_pane");
+        self.resize_process_pane(true)
+    }
+
+    pub fn on_keypress_shrink_pane(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_shrink_pane");
+        self.resize_process_pane(false)
+    }
+
+    /*
+     * The new size is kept for panes joined afterwards
+     */
+    fn resize_process_pane(&self, grow: bool) -> Result<(), Box<dyn Error>> {
+        self.lock_and_load(|state| {
+            let pane_id = match state.current_process().and_then(|p| p.pane_id.as_ref()) {
+                Some(pane_id) => pane_id,
+                None => return Ok(None),
+            };
+            let size = self.tmux_context.resize_pane(
+                pane_id,
+                state.config.layout.sidebar_position(),
+                grow,
+                state.config.layout.resize_step,
+            )?;
+            let gui_state = GUIStateMutation::on(&state.gui_state)
+                .set_process_pane_size(size)
+                .commit();
+            Ok(Some(
+                StateMutation::on(state).set_gui_state(gui_state).commit(),
+            ))
+        })
+    }
+
+    pub fn on_keypress_move_up(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_move_up");
+        self.move_current_process(-1)
+    }
+
+    pub fn on_keypress_move_down(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_move_down");
+        self.move_current_process(1)
+    }
+
+    /*
+     * Reordering is disabled while filtering, since the list may be ranked
+     */
+    fn move_current_process(&self, direction: i8) -> Result<(), Box<dyn Error>> {
+        self.lock_and_load(|state| {
+            if state.gui_state.filter_text.is_some() {
+                return Ok(Some(add_message(
+                    state,
+                    "Clear the filter to reorder processes".to_string(),
+                )));
+            }
+            let new_state = StateMutation::on(state)
+                .move_current_process(direction)
+                .commit();
+            if let Some(order_file) = &new_state.config.general.order_file {
+                if let Err(e) = save_order(order_file, &new_state.processes) {
+                    error!("{}", e);
+                    return Ok(Some(add_message(&new_state, e.to_string())));
                 }
             }
+            Ok(Some(new_state))
         })
     }
 
     pub fn on_keypress_start(&self) -> Result<(), Box<dyn Error>> {
         trace!("on_keypress_start");
+        self.lock_and_load_targets(|state, process| {
+            replace_pane_and_start_process(state, &self.tmux_context, &self.hooks, process)
+        })
+    }
+
+    pub fn on_keypress_restart(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_restart");
+        self.lock_and_load_targets(|state, process| {
+            restart_process(state, &self.tmux_context, &self.hooks, process)
+        })
+    }
+
+    pub fn on_keypress_mark(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_mark");
+        self.lock_and_load(|state| match state.current_process() {
+            Some(process) => {
+                let gui_state = GUIStateMutation::on(&state.gui_state)
+                    .toggle_mark(process.id)
+                    .commit();
+                Ok(Some(
+                    StateMutation::on(state).set_gui_state(gui_state).commit(),
+                ))
+            }
+            None => Ok(None),
+        })
+    }
+
+    pub fn on_keypress_clear_marks(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_clear_marks");
+        self.lock_and_load(|state| {
+            let gui_state = GUIStateMutation::on(&state.gui_state)
+                .clear_marks()
+                .commit();
+            Ok(Some(
+                StateMutation::on(state).set_gui_state(gui_state).commit(),
+            ))
+        })
+    }
+
+    /*
+     * Applies f to every marked process, or the current process if none are marked
+     */
+    fn lock_and_load_targets<F>(&self, f: F) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(&State, &Process) -> Result<State, Box<dyn Error>>,
+    {
         self.lock_and_load(|state| {
             if state.exiting {
-                Ok(None)
-            } else {
-                match state.current_process() {
-                    Some(process) => {
-                        let kill_pane_state = kill_pane(state, process)?.unwrap_or(state.clone());
-
-                        match start_process(&kill_pane_state, &self.tmux_context, process) {
-                            Ok(Some(sp_state)) => {
-                                if process.config.autofocus {
-                                    trace!("Auto-focusing {}", process.label);
-                                    if let Some(e) = focus_active_pane(&sp_state).err() {
-                                        error!("Error auto-focusing {}: {}", process.label, e);
-                                    }
-                                }
-                                Ok(Some(sp_state))
-                            }
-                            Ok(None) => Ok(Some(kill_pane_state)),
-                            Err(e) => {
-                                error!("Error starting process {}: {}", process.label, e);
-                                Ok(Some(kill_pane_state))
-                            }
-                        }
-                    }
-                    None => Ok(None),
+                return Ok(None);
+            }
+            let process_ids: Vec<usize> =
+                state.get_target_processes().iter().map(|p| p.id).collect();
+            if process_ids.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(apply_to_processes(state, &process_ids, &f)))
+        })
+    }
+
+    pub fn on_keypress_start_group(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_start_group");
+        self.lock_and_load_group(|state, process| {
+            replace_pane_and_start_process(state, &self.tmux_context, &self.hooks, process)
+        })
+    }
+
+    pub fn on_keypress_stop_group(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_stop_group");
+        self.lock_and_load_group(|state, process| {
+            Ok(halt_process(state, Some(process))?.unwrap_or(state.clone()))
+        })
+    }
+
+    pub fn on_keypress_restart_group(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_restart_group");
+        self.lock_and_load_group(|state, process| {
+            restart_process(state, &self.tmux_context, &self.hooks, process)
+        })
+    }
+
+    pub fn on_keypress_start_all(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_start_all");
+        self.lock_and_load_all(|state, process| {
+            replace_pane_and_start_process(state, &self.tmux_context, &self.hooks, process)
+        })
+    }
+
+    pub fn on_keypress_stop_all(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_stop_all");
+        self.lock_and_load_all(|state, process| {
+            Ok(halt_process(state, Some(process))?.unwrap_or(state.clone()))
+        })
+    }
+
+    pub fn on_keypress_restart_all(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_keypress_restart_all");
+        self.lock_and_load_all(|state, process| {
+            restart_process(state, &self.tmux_context, &self.hooks, process)
+        })
+    }
+
+    /*
+     * Like the start_all/stop_all/restart_all keybindings, but acting on
+     * every process whatever the filter shows (for the control API)
+     */
+    pub fn on_control_start_all(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_control_start_all");
+        self.lock_and_load_every(|state, process| {
+            replace_pane_and_start_process(state, &self.tmux_context, &self.hooks, process)
+        })
+    }
+
+    pub fn on_control_stop_all(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_control_stop_all");
+        self.lock_and_load_every(|state, process| {
+            Ok(halt_process(state, Some(process))?.unwrap_or(state.clone()))
+        })
+    }
+
+    pub fn on_control_restart_all(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_control_restart_all");
+        self.lock_and_load_every(|state, process| {
+            restart_process(state, &self.tmux_context, &self.hooks, process)
+        })
+    }
+
+    /*
+     * Applies f to the processes bulk actions act on (see scope_bulk_actions_to_filter)
+     */
+    fn lock_and_load_all<F>(&self, f: F) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(&State, &Process) -> Result<State, Box<dyn Error>>,
+    {
+        self.lock_and_load_processes(State::get_bulk_action_processes, f)
+    }
+
+    /*
+     * Applies f to every process, whether the filter shows it or not
+     */
+    fn lock_and_load_every<F>(&self, f: F) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(&State, &Process) -> Result<State, Box<dyn Error>>,
+    {
+        self.lock_and_load_processes(|state| state.processes.iter().collect(), f)
+    }
+
+    fn lock_and_load_processes<P, F>(&self, processes: P, f: F) -> Result<(), Box<dyn Error>>
+    where
+        P: Fn(&State) -> Vec<&Process>,
+        F: Fn(&State, &Process) -> Result<State, Box<dyn Error>>,
+    {
+        self.lock_and_load(|state| {
+            if state.exiting {
+                return Ok(None);
+            }
+            let process_ids: Vec<usize> = processes(state).iter().map(|p| p.id).collect();
+            Ok(Some(apply_to_processes(state, &process_ids, &f)))
+        })
+    }
+
+    /*
+     * Applies f to every process of the group being filtered by
+     */
+    fn lock_and_load_group<F>(&self, f: F) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(&State, &Process) -> Result<State, Box<dyn Error>>,
+    {
+        self.lock_and_load(|state| {
+            if state.exiting {
+                return Ok(None);
+            }
+            match state.filtered_group() {
+                Some(group) => {
+                    info!("Applying group action to group: {}", group);
+                    let process_ids: Vec<usize> = state
+                        .get_group_processes(&group)
+                        .iter()
+                        .map(|p| p.id)
+                        .collect();
+                    Ok(Some(apply_to_processes(state, &process_ids, &f)))
                 }
+                None => Ok(Some(add_message(
+                    state,
+                    format!(
+                        "Filter by {}<name> to select a group",
+                        state.config.layout.group_search_prefix
+                    ),
+                ))),
             }
         })
     }
 
     pub fn on_keypress_stop(&self) -> Result<(), Box<dyn Error>> {
         trace!("on_keypress_stop");
-        self.lock_and_load(|state| halt_process(state, state.current_process()))
+        self.lock_and_load_targets(|state, process| {
+            Ok(halt_process(state, Some(process))?.unwrap_or(state.clone()))
+        })
     }
 
     pub fn on_keypress_switch_focus(&self) -> Result<(), Box<dyn Error>> {
         trace!("on_keypress_switch_focus");
-        self.lock_and_load(|state| focus_active_pane(state))
+        self.lock_and_load(|state| focus_active_pane(state, &self.tmux_context))
     }
 
-    pub fn on_pid_terminated(&self, pid: i32) -> Result<(), Box<dyn Error>> {
-        trace!("on_pid_terminated: {}", pid);
+    pub fn on_pid_terminated(
+        &self,
+        pid: i32,
+        exit_code: Option<i32>,
+    ) -> Result<(), Box<dyn Error>> {
+        trace!("on_pid_terminated: {} (exit code: {:?})", pid, exit_code);
+        let notifications = RefCell::new(vec![]);
         self.lock_and_load(|state| {
-            let process = state.get_process_by_pid(pid);
-            let new_state = set_process_terminated(state, process);
-            if new_state.is_some() {
-                info!("pid terminated: {}", pid);
-                if let Some(e) = tmux::select_pane(&self.tmux_context.pane_id).err() {
-                    error!(
-                        "Error focusing proctmux pane after pid {} termination: {}",
-                        pid, e
-                    );
+            let process = match state.get_process_by_pid(pid) {
+                Some(process) => process,
+                None => return Ok(Some(state.clone())),
+            };
+            let mut new_state = match set_process_terminated(state, Some(process), exit_code) {
+                Some(new_state) => new_state,
+                None => return Ok(Some(state.clone())),
+            };
+            info!("pid terminated: {}", pid);
+            if let Some(e) = self
+                .tmux_context
+                .select_pane(&self.tmux_context.pane_id)
+                .err()
+            {
+                error!(
+                    "Error focusing proctmux pane after pid {} termination: {}",
+                    pid, e
+                );
+            }
+
+            // processes halted by the user, or while quitting, exit as expected
+            let unexpected = process.status == ProcessStatus::Running && !state.exiting;
+            if unexpected {
+                self.notify_terminated(&notifications, state, process, exit_code);
+            }
+
+            let mut hooks = vec![Hook::AfterStop];
+            if unexpected && exit_code != Some(0) {
+                hooks.push(Hook::OnFail);
+            }
+            self.hooks.run(&hooks, process, Some(pid), exit_code);
+
+            if process.restart_pending && !state.exiting {
+                info!("Restarting {}", process.label);
+                new_state = StateMutation::on(&new_state)
+                    .set_process_restart_pending(false, process.id)
+                    .commit();
+                if let Some(process) = new_state.get_process(process.id) {
+                    new_state = replace_pane_and_start_process(
+                        &new_state,
+                        &self.tmux_context,
+                        &self.hooks,
+                        process,
+                    )?;
+                }
+            }
+            Ok(Some(new_state))
+        })?;
+        self.send_notifications(notifications);
+        Ok(())
+    }
+
+    /*
+     * Starts the process once its before_start hook succeeded
+     */
+    pub fn on_before_start_done(
+        &self,
+        process_id: usize,
+        error: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        trace!("on_before_start_done: {}", process_id);
+        self.lock_and_load(|state| {
+            let new_state = StateMutation::on(state)
+                .set_process_starting(false, process_id)
+                .commit();
+            if let Some(e) = &error {
+                return Ok(Some(add_message(&new_state, e.clone())));
+            }
+            let process = match new_state.get_process(process_id) {
+                Some(process) if !new_state.exiting => process.clone(),
+                _ => return Ok(Some(new_state)),
+            };
+            match create_process_pane(&new_state, &self.tmux_context, &self.hooks, &process) {
+                Ok(started_state) => {
+                    autofocus(&started_state, &self.tmux_context, &process);
+                    Ok(Some(started_state))
+                }
+                Err(e) => {
+                    error!("Error starting process {}: {}", process.label, e);
+                    Ok(Some(new_state))
                 }
             }
-            Ok(new_state.or(Some(state.clone())))
         })
     }
 
-    pub fn check_for_exit(&self, state: &State) {
-        if state.exiting {
-            if state
-                .processes
-                .iter()
-                .find(|p| p.status != ProcessStatus::Halted)
-                .is_none()
+    pub fn on_pane_output(&self, pane_id: &str, line: &str) -> Result<(), Box<dyn Error>> {
+        let notifications = RefCell::new(vec![]);
+        self.lock_and_load(|state| {
+            let process = match state.get_process_by_pane_id(pane_id) {
+                Some(process) => process,
+                None => return Ok(None),
+            };
+            let mut mutation = None;
+            if !process.ready
+                && process
+                    .ready_rule
+                    .as_ref()
+                    .is_some_and(|rule| rule.is_match(line))
             {
-                self.running
-                    .store(false, std::sync::atomic::Ordering::Relaxed);
+                trace!("Ready: {}", process.label);
+                self.notify(
+                    &notifications,
+                    state,
+                    NotificationEvent::Ready,
+                    process,
+                    &format!("{} is ready", process.label),
+                );
+                mutation = Some(StateMutation::on(state).set_process_ready(true, process.id));
+            }
+            if process.id != state.current_proc_id {
+                if let Some(level) = match_alert_rules(&process.alert_rules, line) {
+                    trace!("Alert ({:?}) for {}: {}", level, process.label, line);
+                    if process.alert < Some(level) {
+                        self.notify(
+                            &notifications,
+                            state,
+                            NotificationEvent::Alert,
+                            process,
+                            &format!("{}: {}", process.label, line.trim()),
+                        );
+                    }
+                    mutation = Some(
+                        mutation
+                            .unwrap_or(StateMutation::on(state))
+                            .add_process_alert(level, process.id),
+                    );
+                }
             }
+            Ok(mutation.map(|m| m.commit()))
+        })?;
+        self.send_notifications(notifications);
+        Ok(())
+    }
+
+    fn notify_terminated(
+        &self,
+        notifications: &PendingNotifications,
+        state: &State,
+        process: &Process,
+        exit_code: Option<i32>,
+    ) {
+        let events = &state.config.notifications.events;
+        let failed = exit_code != Some(0);
+        let exit_status = exit_code.map_or("killed".to_string(), |code| format!("code {}", code));
+        if failed && events.contains(&NotificationEvent::Fail) {
+            self.notify(
+                notifications,
+                state,
+                NotificationEvent::Fail,
+                process,
+                &format!("{} failed ({})", process.label, exit_status),
+            );
+        } else {
+            self.notify(
+                notifications,
+                state,
+                NotificationEvent::Exit,
+                process,
+                &format!("{} exited ({})", process.label, exit_status),
+            );
+        }
+    }
+
+    /*
+     * The pids whose usage is sampled, so the sampling itself can happen
+     * without holding the controller
+     */
+    pub fn process_pids(&self) -> Vec<i32> {
+        match self.state.lock() {
+            Ok(state) => state.processes.iter().filter_map(|p| p.pid).collect(),
+            Err(e) => {
+                error!("process_pids => Failed to lock state: {}", e);
+                vec![]
+            }
+        }
+    }
+
+    /*
+     * Records the usage of each running process tree (and redraws, which also
+     * keeps durations in the process list current).
+     * Notifies when a process first exceeds a usage threshold.
+     */
+    pub fn on_resource_sample(
+        &self,
+        usages: &HashMap<i32, ResourceUsage>,
+    ) -> Result<(), Box<dyn Error>> {
+        trace!("on_resource_sample");
+        let notifications = RefCell::new(vec![]);
+        self.lock_and_load(|state| {
+            let config = &state.config.resources;
+            let mut mutation = StateMutation::on(state);
+            for process in state.processes.iter() {
+                let usage = process.pid.and_then(|pid| usages.get(&pid)).copied();
+                if let Some(usage) = &usage {
+                    let exceeded = exceeds_threshold(config, usage);
+                    let exceeded_before = process
+                        .usage
+                        .as_ref()
+                        .is_some_and(|u| exceeds_threshold(config, u));
+                    if exceeded && !exceeded_before {
+                        self.notify(
+                            &notifications,
+                            state,
+                            NotificationEvent::Usage,
+                            process,
+                            &format!(
+                                "{} is using {:.0}% CPU, {} memory",
+                                process.label,
+                                usage.cpu_percent,
+                                format_bytes(usage.memory_bytes)
+                            ),
+                        );
+                    }
+                }
+                mutation = mutation.set_process_usage(usage, process.id);
+            }
+            Ok(Some(mutation.commit()))
+        })?;
+        self.send_notifications(notifications);
+        Ok(())
+    }
+
+    /*
+     * Brings the state in line with the panes tmux has: catches up on exits
+     * which were missed (e.g. while a control mode client was restarting),
+     * and forgets panes which were killed outside of proctmux. The processes
+     * of those panes were killed with them.
+     */
+    pub fn on_reconcile(&self) -> Result<(), Box<dyn Error>> {
+        trace!("on_reconcile");
+        let panes = self.tmux_context.panes()?;
+        let (closed, mut exited, reaped) = match self.state.lock() {
+            Ok(state) => (
+                closed_panes(&state, &panes),
+                exited_processes(&state, &panes),
+                reaped_exit_codes(&state, &panes),
+            ),
+            Err(e) => {
+                error!("on_reconcile => Failed to lock state: {}", e);
+                return Ok(());
+            }
+        };
+        if !closed.is_empty() {
+            self.lock_and_load(|state| {
+                Ok(Some(closed.iter().fold(state.clone(), |acc, process| {
+                    warn!("The pane of {} was closed, forgetting it", process.label);
+                    forget_pane(&acc, process.id)
+                })))
+            })?;
+        }
+        exited.extend(
+            closed
+                .iter()
+                .filter(|p| p.status != ProcessStatus::Halted)
+                .filter_map(|p| p.pid.map(|pid| (pid, None))),
+        );
+        for (pid, exit_code) in exited {
+            warn!("Missed the exit of pid {}, reconciling", pid);
+            self.on_pid_terminated(pid, exit_code)?;
+        }
+        if !reaped.is_empty() {
+            self.lock_and_load(|state| {
+                Ok(Some(reaped.iter().fold(
+                    state.clone(),
+                    |acc, (process_id, exit_code)| {
+                        StateMutation::on(&acc)
+                            .set_process_exit_code(Some(*exit_code), *process_id)
+                            .set_process_last_exit_code(Some(*exit_code), *process_id)
+                            .commit()
+                    },
+                )))
+            })?;
+        }
+        Ok(())
+    }
+
+    /*
+     * Notifying runs tmux and the notification command, so notifications are
+     * only queued while the state is locked, and sent once it is unlocked
+     */
+    fn notify(
+        &self,
+        notifications: &PendingNotifications,
+        state: &State,
+        event: NotificationEvent,
+        process: &Process,
+        message: &str,
+    ) {
+        notifications.borrow_mut().push(PendingNotification {
+            config: state.config.notifications.clone(),
+            event,
+            process: process.clone(),
+            message: message.to_string(),
+        });
+    }
+
+    fn send_notifications(&self, notifications: PendingNotifications) {
+        for n in notifications.into_inner() {
+            notify(
+                &n.config,
+                &self.stdout,
+                &self.tmux_context,
+                n.event,
+                &n.process,
+                &n.message,
+            );
+        }
+    }
+
+    pub fn check_for_exit(&self, state: &State) {
+        if is_exit_complete(state) {
+            self.running
+                .store(false, std::sync::atomic::Ordering::Relaxed);
         }
     }
 }
 
+/*
+ * Selects the next (direction 1) or previous (-1) entry of the process list,
+ * showing the pane of the newly selected process beside the sidebar
+ * (or marking it, in split view)
+ */
+fn move_selection(
+    state: &State,
+    tmux_context: &TmuxContext,
+    direction: i8,
+) -> Result<State, Box<dyn Error>> {
+    break_pane(state, tmux_context, state.current_proc_id)?;
+    let mutation = StateMutation::on(state);
+    let new_state = if direction > 0 {
+        mutation.next_process()
+    } else {
+        mutation.previous_process()
+    }
+    .commit();
+    if let Err(e) = join_pane(&new_state, tmux_context, new_state.current_proc_id) {
+        error!(
+            "Error joining pane (proc id: {}): {}",
+            new_state.current_proc_id, e
+        );
+    }
+    if let Err(e) = mark_selected_pane(&new_state, tmux_context) {
+        error!(
+            "Error marking pane (proc id: {}): {}",
+            new_state.current_proc_id, e
+        );
+    }
+    Ok(new_state)
+}
+
+/*
+ * Halts every running process, proctmux exits once all of them are halted
+ */
+fn halt_all_and_exit(state: &State) -> State {
+    let new_state = StateMutation::on(state).set_exiting().commit();
+    new_state
+        .processes
+        .iter()
+        .filter(|process| process.status != ProcessStatus::Halted)
+        .fold(new_state.clone(), |acc, process| {
+            match halt_process(&acc, Some(process)) {
+                Ok(Some(s)) => s,
+                Ok(None) => acc,
+                Err(e) => {
+                    error!(
+                        "Error halting process {} in on_keypress_quit: {}",
+                        process.label, e
+                    );
+                    acc
+                }
+            }
+        })
+}
+
+fn is_exit_complete(state: &State) -> bool {
+    state.exiting
+        && !state
+            .processes
+            .iter()
+            .any(|p| p.status != ProcessStatus::Halted)
+}
+
+fn kill_halted_panes(state: &State, tmux_context: &TmuxContext) -> State {
+    state
+        .processes
+        .iter()
+        .filter(|process| process.status == ProcessStatus::Halted)
+        .fold(state.clone(), |acc, process| {
+            match kill_pane(&acc, tmux_context, process) {
+                Ok(Some(s)) => s,
+                Ok(None) => acc,
+                Err(e) => {
+                    error!(
+                        "Error killing pane for process {} in on_exit: {}",
+                        process.label, e
+                    );
+                    acc
+                }
+            }
+        })
+}
+
 fn start_process(
     state: &State,
     tmux_context: &TmuxContext,
+    hooks: &HookRunner,
     process: &Process,
 ) -> Result<Option<State>, Box<dyn Error>> {
-    if process.status != ProcessStatus::Halted {
+    if process.status != ProcessStatus::Halted || process.starting {
         return Ok(None);
     }
+    if Hook::BeforeStart.command(process).is_some() {
+        hooks.run_before_start(process);
+        return Ok(Some(
+            StateMutation::on(state)
+                .set_process_starting(true, process.id)
+                .commit(),
+        ));
+    }
+    create_process_pane(state, tmux_context, hooks, process).map(Some)
+}
 
-    let new_pane = if process.id == state.current_proc_id {
-        tmux_context.create_pane(process)
+/*
+ * Starts the process in a new pane, once its before_start hook (if any) succeeded
+ */
+fn create_process_pane(
+    state: &State,
+    tmux_context: &TmuxContext,
+    hooks: &HookRunner,
+    process: &Process,
+) -> Result<State, Box<dyn Error>> {
+    let in_split_view = !state.gui_state.split_view_proc_ids.is_empty();
+    let new_pane = if process.id == state.current_proc_id && !in_split_view {
+        tmux_context.create_pane(
+            process,
+            state.config.layout.sidebar_position(),
+            &state.gui_state.process_pane_size,
+        )
     } else {
         tmux_context.create_detached_pane(process)
     };
@@ -344,61 +1028,242 @@ fn start_process(
                 process.label,
                 pid.unwrap_or(-1)
             );
-            Ok(Some(
-                StateMutation::on(state)
-                    .set_process_status(ProcessStatus::Running, process.id)
-                    .set_process_pane_id(Some(pane_id), process.id)
-                    .set_process_pid(pid, process.id)
-                    .commit(),
-            ))
+            let new_state = StateMutation::on(state)
+                .set_process_status(ProcessStatus::Running, process.id)
+                .set_process_pane_id(Some(pane_id), process.id)
+                .set_process_pid(pid, process.id)
+                .set_process_exit_code(None, process.id)
+                .set_process_started_at(Some(Instant::now()), process.id)
+                .set_process_run_duration(None, process.id)
+                .set_process_restart_count(
+                    process.restart_count + process.started_at.is_some() as usize,
+                    process.id,
+                )
+                .set_process_ready(false, process.id)
+                .clear_process_alerts(process.id)
+                .commit();
+            if state.gui_state.split_view_proc_ids.contains(&process.id) {
+                if let Err(e) = layout_split_view(&new_state, tmux_context) {
+                    error!("Error adding {} to the split view: {}", process.label, e);
+                }
+            }
+            hooks.run(&[Hook::AfterStart], process, pid, None);
+            Ok(new_state)
         }
         Err(e) => Err(e),
     }
 }
 
-fn kill_pane(state: &State, process: &Process) -> Result<Option<State>, Box<dyn Error>> {
+/*
+ * Kills the pane left behind by a previous run (if any) and starts the process in a new one
+ */
+fn replace_pane_and_start_process(
+    state: &State,
+    tmux_context: &TmuxContext,
+    hooks: &HookRunner,
+    process: &Process,
+) -> Result<State, Box<dyn Error>> {
+    let kill_pane_state = kill_pane(state, tmux_context, process)?.unwrap_or(state.clone());
+
+    match start_process(&kill_pane_state, tmux_context, hooks, process) {
+        Ok(Some(sp_state)) => {
+            autofocus(&sp_state, tmux_context, process);
+            Ok(sp_state)
+        }
+        Ok(None) => Ok(kill_pane_state),
+        Err(e) => {
+            error!("Error starting process {}: {}", process.label, e);
+            Ok(kill_pane_state)
+        }
+    }
+}
+
+/*
+ * Focuses the pane of a process which was just started, if it is configured to
+ */
+fn autofocus(state: &State, tmux_context: &TmuxContext, process: &Process) {
+    let started = state
+        .get_process(process.id)
+        .is_some_and(|p| p.status == ProcessStatus::Running);
+    if started && process.config.autofocus && process.id == state.current_proc_id {
+        trace!("Auto-focusing {}", process.label);
+        if let Some(e) = focus_active_pane(state, tmux_context).err() {
+            error!("Error auto-focusing {}: {}", process.label, e);
+        }
+    }
+}
+
+/*
+ * Starts a halted process, or halts a running process and
+ * starts it again once its termination is received
+ */
+fn restart_process(
+    state: &State,
+    tmux_context: &TmuxContext,
+    hooks: &HookRunner,
+    process: &Process,
+) -> Result<State, Box<dyn Error>> {
+    match process.status {
+        ProcessStatus::Halted => {
+            replace_pane_and_start_process(state, tmux_context, hooks, process)
+        }
+        ProcessStatus::Running => {
+            let halted_state = halt_process(state, Some(process))?.unwrap_or(state.clone());
+            Ok(StateMutation::on(&halted_state)
+                .set_process_restart_pending(true, process.id)
+                .commit())
+        }
+        ProcessStatus::Halting => Ok(StateMutation::on(state)
+            .set_process_restart_pending(true, process.id)
+            .commit()),
+    }
+}
+
+/*
+ * Applies f to each process in turn, each application receiving the state
+ * resulting from the previous one. Errors are logged and the process skipped.
+ */
+fn apply_to_processes<F>(state: &State, process_ids: &[usize], f: &F) -> State
+where
+    F: Fn(&State, &Process) -> Result<State, Box<dyn Error>>,
+{
+    process_ids.iter().fold(state.clone(), |acc, process_id| {
+        let process = match acc.get_process(*process_id) {
+            Some(process) => process.clone(),
+            None => return acc,
+        };
+        match f(&acc, &process) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Error applying action to process {}: {}", process.label, e);
+                acc
+            }
+        }
+    })
+}
+
+fn add_message(state: &State, message: String) -> State {
+    let gui_state = GUIStateMutation::on(&state.gui_state)
+        .add_message(message)
+        .commit();
+    StateMutation::on(state).set_gui_state(gui_state).commit()
+}
+
+fn kill_pane(
+    state: &State,
+    tmux_context: &TmuxContext,
+    process: &Process,
+) -> Result<Option<State>, Box<dyn Error>> {
     if process.status != ProcessStatus::Halted {
         return Ok(None);
     }
 
     match &process.pane_id {
         Some(pane_id) => {
-            // TODO: will this error if pane id value exists but pane does not?
-            match tmux::kill_pane(pane_id) {
-                Ok(_) => Ok(Some(
-                    StateMutation::on(state)
-                        .set_process_pane_id(None, process.id)
-                        .commit(),
-                )),
-                Err(e) => return Err(Box::new(e)),
-            }
+            // a pane which was killed outside of proctmux is gone all the same
+            ignore_missing_pane(tmux_context.kill_pane(pane_id))?;
+            Ok(Some(forget_pane(state, process.id)))
         }
         None => Ok(None),
     }
 }
 
-fn focus_active_pane(state: &State) -> Result<Option<State>, Box<dyn Error>> {
+fn forget_pane(state: &State, process_id: usize) -> State {
+    StateMutation::on(&set_popped_out(state, process_id, false))
+        .set_process_pane_id(None, process_id)
+        .commit()
+}
+
+/*
+ * tmux not finding the pane of a process means it was killed outside of
+ * proctmux. on_reconcile forgets it, until then it is skipped.
+ */
+fn ignore_missing_pane(result: Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+    match result {
+        Err(e) if e.is::<PaneNotFound>() => {
+            warn!("{}", e);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn focus_active_pane(
+    state: &State,
+    tmux_context: &TmuxContext,
+) -> Result<Option<State>, Box<dyn Error>> {
     match state.current_process().and_then(|p| p.pane_id.clone()) {
-        Some(pane_id) => Ok(tmux::select_pane(&pane_id).map(|_| None)?),
+        Some(pane_id) => Ok(tmux_context.select_pane(&pane_id).map(|_| None)?),
         None => Ok(None),
     }
 }
 
-fn set_process_terminated(state: &State, process: Option<&Process>) -> Option<State> {
-    process
-        .map(|p| {
-            if p.status != ProcessStatus::Halted {
-                Some(
-                    StateMutation::on(state)
-                        .set_process_status(ProcessStatus::Halted, p.id)
-                        .set_process_pid(None, p.id)
-                        .commit(),
-                )
-            } else {
-                None
-            }
+/*
+ * The pids (and exit codes) of the processes which have not halted according
+ * to the state, but whose pane tmux has as dead
+ */
+fn exited_processes(state: &State, panes: &[PaneState]) -> Vec<DeadPane> {
+    panes
+        .iter()
+        .filter_map(|pane| pane.dead)
+        .filter(|(pid, _)| state.get_process_by_pid(*pid).is_some())
+        .collect()
+}
+
+/*
+ * The exit codes of halted processes which halted before tmux had reaped
+ * them (so without an exit code), now that tmux has
+ */
+fn reaped_exit_codes(state: &State, panes: &[PaneState]) -> Vec<(usize, i32)> {
+    state
+        .processes
+        .iter()
+        .filter(|p| p.status == ProcessStatus::Halted && p.exit_code.is_none())
+        .filter_map(|p| {
+            let pane_id = p.pane_id.as_ref()?;
+            let (_, exit_code) = panes.iter().find(|pane| &pane.pane_id == pane_id)?.dead?;
+            Some((p.id, exit_code?))
         })
-        .flatten()
+        .collect()
+}
+
+/*
+ * The processes with a pane which tmux does not have (anymore)
+ */
+fn closed_panes(state: &State, panes: &[PaneState]) -> Vec<Process> {
+    state
+        .processes
+        .iter()
+        .filter(|p| {
+            p.pane_id
+                .as_ref()
+                .is_some_and(|pane_id| !panes.iter().any(|pane| &pane.pane_id == pane_id))
+        })
+        .cloned()
+        .collect()
+}
+
+fn set_process_terminated(
+    state: &State,
+    process: Option<&Process>,
+    exit_code: Option<i32>,
+) -> Option<State> {
+    process.and_then(|p| {
+        if p.status != ProcessStatus::Halted {
+            Some(
+                StateMutation::on(state)
+                    .set_process_status(ProcessStatus::Halted, p.id)
+                    .set_process_pid(None, p.id)
+                    .set_process_exit_code(exit_code, p.id)
+                    .set_process_last_exit_code(exit_code, p.id)
+                    .set_process_exited_at(Some(Instant::now()), p.id)
+                    .set_process_run_duration(p.started_at.map(|t| t.elapsed()), p.id)
+                    .commit(),
+            )
+        } else {
+            None
+        }
+    })
 }
 
 fn halt_process(state: &State, process: Option<&Process>) -> Result<Option<State>, Box<dyn Error>> {
@@ -428,14 +1293,25 @@ fn halt_process(state: &State, process: Option<&Process>) -> Result<Option<State
     }
 }
 
+/*
+ * Selecting a process only breaks/joins its pane outside of split view,
+ * in split view the joined panes stay put (see mark_selected_pane).
+ * Popped out panes are left wherever they are.
+ */
 fn break_pane(
     state: &State,
     tmux_context: &TmuxContext,
     process_id: usize,
 ) -> Result<(), Box<dyn Error>> {
+    if !state.gui_state.split_view_proc_ids.is_empty() {
+        return Ok(());
+    }
     if let Some(process) = state.get_process(process_id) {
+        if state.gui_state.popped_out_proc_ids.contains(&process.id) {
+            return Ok(());
+        }
         if let Some(pane_id) = &process.pane_id {
-            tmux_context.break_pane(pane_id, process.id, &process.label)?;
+            ignore_missing_pane(tmux_context.break_pane(pane_id, process.id, &process.label))?;
         }
     }
     Ok(())
@@ -446,10 +1322,580 @@ fn join_pane(
     tmux_context: &TmuxContext,
     process_id: usize,
 ) -> Result<(), Box<dyn Error>> {
+    if !state.gui_state.split_view_proc_ids.is_empty() {
+        return Ok(());
+    }
     if let Some(process) = state.get_process(process_id) {
+        if state.gui_state.popped_out_proc_ids.contains(&process.id) {
+            return Ok(());
+        }
         if let Some(pane_id) = &process.pane_id {
-            tmux_context.join_pane(pane_id)?;
+            ignore_missing_pane(tmux_context.join_pane(
+                pane_id,
+                state.config.layout.sidebar_position(),
+                &state.gui_state.process_pane_size,
+            ))?;
         }
     }
     Ok(())
 }
+
+fn split_view_processes(state: &State) -> Vec<&Process> {
+    state
+        .gui_state
+        .split_view_proc_ids
+        .iter()
+        .filter_map(|id| state.get_process(*id))
+        .collect()
+}
+
+/*
+ * (Re)joins the panes of the split view processes beside the sidebar.
+ * Panes already joined are broken out first, so the layout starts from scratch.
+ */
+fn layout_split_view(state: &State, tmux_context: &TmuxContext) -> Result<(), Box<dyn Error>> {
+    let processes: Vec<&Process> = split_view_processes(state)
+        .into_iter()
+        .filter(|p| p.pane_id.is_some() && !state.gui_state.popped_out_proc_ids.contains(&p.id))
+        .collect();
+    for process in processes.iter() {
+        if let Some(pane_id) = &process.pane_id {
+            ignore_missing_pane(tmux_context.break_pane(pane_id, process.id, &process.label))?;
+        }
+    }
+    let pane_ids: Vec<&str> = processes
+        .iter()
+        .filter_map(|p| p.pane_id.as_deref())
+        .collect();
+    tmux_context.join_split_view(
+        &pane_ids,
+        state.config.layout.split_view_layout,
+        state.config.layout.sidebar_position(),
+        &state.gui_state.process_pane_size,
+    )?;
+    mark_selected_pane(state, tmux_context)
+}
+
+/*
+ * In split view, marks the pane of the selected process so it stands out
+ * among the others. The sidebar keeps focus, so navigation keeps working.
+ */
+fn mark_selected_pane(state: &State, tmux_context: &TmuxContext) -> Result<(), Box<dyn Error>> {
+    if state.gui_state.split_view_proc_ids.is_empty() {
+        return Ok(());
+    }
+    let pane_id = state
+        .current_process()
+        .filter(|p| {
+            state.gui_state.split_view_proc_ids.contains(&p.id)
+                && !state.gui_state.popped_out_proc_ids.contains(&p.id)
+        })
+        .and_then(|p| p.pane_id.as_deref());
+    ignore_missing_pane(tmux_context.mark_pane(pane_id))
+}
+
+/*
+ * Moves the pane of the selected process into its own window in the
+ * current session, or back beside the sidebar if it is already popped out
+ */
+fn toggle_pop_out(
+    state: &State,
+    tmux_context: &TmuxContext,
+) -> Result<Option<State>, Box<dyn Error>> {
+    let process = match state.current_process() {
+        Some(process) => process,
+        None => return Ok(None),
+    };
+    let pane_id = match &process.pane_id {
+        Some(pane_id) => pane_id,
+        None => {
+            return Ok(Some(add_message(
+                state,
+                format!("{} has no pane to pop out", process.label),
+            )))
+        }
+    };
+    if state.gui_state.popped_out_proc_ids.contains(&process.id) {
+        tmux_context.break_pane(pane_id, process.id, &process.label)?;
+        let new_state = set_popped_out(state, process.id, false);
+        return_pane(&new_state, tmux_context, process.id)?;
+        Ok(Some(new_state))
+    } else {
+        tmux_context.pop_out_pane(pane_id, &process.label)?;
+        let new_state = set_popped_out(state, process.id, true);
+        if new_state
+            .gui_state
+            .split_view_proc_ids
+            .contains(&process.id)
+        {
+            layout_split_view(&new_state, tmux_context)?;
+        }
+        Ok(Some(new_state))
+    }
+}
+
+/*
+ * Shows the pane of the selected process in a popup, which is left in popup
+ */
+fn open_popup(
+    state: &State,
+    tmux_context: &TmuxContext,
+    popup: &RefCell<Option<(usize, Child)>>,
+) -> Result<Option<State>, Box<dyn Error>> {
+    let version = tmux_context.version();
+    if !version.supports_popup() {
+        return Ok(Some(add_message(
+            state,
+            format!("Popups require tmux 3.2 or later, found {}", version),
+        )));
+    }
+    let process = match state.current_process() {
+        Some(process) => process,
+        None => return Ok(None),
+    };
+    let pane_id = match &process.pane_id {
+        Some(pane_id) => pane_id,
+        None => {
+            return Ok(Some(add_message(
+                state,
+                format!("{} has no pane to show", process.label),
+            )))
+        }
+    };
+    if state.gui_state.popped_out_proc_ids.contains(&process.id) {
+        return Ok(Some(add_message(
+            state,
+            format!("{} is already popped out", process.label),
+        )));
+    }
+    // the popup shows the window of the process in the detached session
+    tmux_context.break_pane(pane_id, process.id, &process.label)?;
+    let new_state = set_popped_out(state, process.id, true);
+    if new_state
+        .gui_state
+        .split_view_proc_ids
+        .contains(&process.id)
+    {
+        layout_split_view(&new_state, tmux_context)?;
+    }
+    match tmux_context.popup_window(process.id, &process.label) {
+        Ok(child) => {
+            *popup.borrow_mut() = Some((process.id, child));
+            Ok(Some(new_state))
+        }
+        Err(e) => {
+            return_pane(state, tmux_context, process.id)?;
+            Err(e)
+        }
+    }
+}
+
+fn close_popup(
+    state: &State,
+    tmux_context: &TmuxContext,
+    process_id: usize,
+) -> Result<Option<State>, Box<dyn Error>> {
+    if !state.gui_state.popped_out_proc_ids.contains(&process_id) {
+        return Ok(None);
+    }
+    let new_state = set_popped_out(state, process_id, false);
+    return_pane(&new_state, tmux_context, process_id)?;
+    Ok(Some(new_state))
+}
+
+fn set_popped_out(state: &State, process_id: usize, popped_out: bool) -> State {
+    let gui_state = GUIStateMutation::on(&state.gui_state)
+        .set_popped_out(process_id, popped_out)
+        .commit();
+    StateMutation::on(state).set_gui_state(gui_state).commit()
+}
+
+/*
+ * Joins the pane of a process which is back in the detached session to
+ * the split view or beside the sidebar, if it is meant to be shown there
+ */
+fn return_pane(
+    state: &State,
+    tmux_context: &TmuxContext,
+    process_id: usize,
+) -> Result<(), Box<dyn Error>> {
+    if state.gui_state.split_view_proc_ids.contains(&process_id) {
+        layout_split_view(state, tmux_context)
+    } else if process_id == state.current_proc_id {
+        join_pane(state, tmux_context, process_id)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProcTmuxConfig;
+    use crate::fake_tmux::{FakeTmux, PaneLocation};
+    use crate::hooks::HookEvent;
+    use crate::tmux::TmuxVersion;
+    use crate::tmux_backend::TmuxBackend;
+    use crate::tmux_daemon::TmuxEvent;
+    use std::sync::mpsc::channel;
+
+    fn setup() -> (State, TmuxContext, FakeTmux) {
+        let config: ProcTmuxConfig = serde_yaml::from_str(
+            r#"
+log_file: /dev/null
+keybinding: {}
+layout:
+  sort_process_list_alpha: false
+procs:
+  web:
+    shell: "npm start"
+  worker:
+    shell: "cargo run"
+"#,
+        )
+        .unwrap();
+        let fake = FakeTmux::new();
+        let tmux_context = TmuxContext::with_backend(Box::new(fake.clone()), "%0", "$0", "$1");
+        tmux_context.prepare().unwrap();
+        (State::new(&config), tmux_context, fake)
+    }
+
+    /*
+     * Hooks whose completions are not received
+     */
+    fn hooks() -> HookRunner {
+        HookRunner::new(channel().0)
+    }
+
+    fn process<'a>(state: &'a State, label: &str) -> &'a Process {
+        state.processes.iter().find(|p| p.label == label).unwrap()
+    }
+
+    fn start(state: &State, tmux_context: &TmuxContext, label: &str) -> State {
+        let process = process(state, label).clone();
+        start_process(state, tmux_context, &hooks(), &process)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn pane_id(state: &State, label: &str) -> String {
+        process(state, label).pane_id.clone().unwrap()
+    }
+
+    /*
+     * Simulates the process exiting, applying the event like on_pid_terminated
+     */
+    fn exit(state: &State, fake: &FakeTmux, label: &str, exit_code: Option<i32>) -> State {
+        match fake.exit_pane(&pane_id(state, label), exit_code) {
+            Some(TmuxEvent::PaneDead { pid, exit_code }) => {
+                set_process_terminated(state, state.get_process_by_pid(pid), exit_code).unwrap()
+            }
+            event => panic!("Unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn starts_the_selected_process_beside_the_sidebar() {
+        let (state, tmux_context, fake) = setup();
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        let state = start(&state, &tmux_context, "web");
+        let state = start(&state, &tmux_context, "worker");
+
+        let web = process(&state, "web");
+        assert_eq!(web.status, ProcessStatus::Running);
+        assert_eq!(
+            web.pid,
+            Some(fake.pane(&pane_id(&state, "web")).unwrap().pid)
+        );
+        assert_eq!(fake.panes_beside("%0"), vec![pane_id(&state, "web")]);
+
+        let worker = fake.pane(&pane_id(&state, "worker")).unwrap();
+        assert_eq!(worker.location, PaneLocation::Window("$1".to_string()));
+        assert_eq!(worker.command, Some("cargo run".to_string()));
+        assert!(worker.remain_on_exit);
+    }
+
+    #[test]
+    fn halted_processes_keep_their_pane_until_restarted() {
+        let (state, tmux_context, fake) = setup();
+        let state = start(&state, &tmux_context, "web");
+        let web = process(&state, "web").clone();
+
+        let state = halt_process(&state, Some(&web)).unwrap().unwrap();
+        assert_eq!(process(&state, "web").status, ProcessStatus::Halting);
+
+        let state = exit(&state, &fake, "web", Some(2));
+        let web = process(&state, "web").clone();
+        assert_eq!(web.status, ProcessStatus::Halted);
+        assert_eq!(web.pid, None);
+        assert_eq!(web.last_exit_code, Some(2));
+        assert!(fake.pane(web.pane_id.as_ref().unwrap()).unwrap().dead);
+
+        let state = replace_pane_and_start_process(&state, &tmux_context, &hooks(), &web).unwrap();
+        let restarted = process(&state, "web");
+        assert_eq!(restarted.status, ProcessStatus::Running);
+        assert_eq!(restarted.restart_count, 1);
+        assert!(fake.pane(web.pane_id.as_ref().unwrap()).is_none());
+    }
+
+    #[test]
+    fn navigation_only_shows_the_selected_pane() {
+        let (state, tmux_context, fake) = setup();
+        let state = start(&state, &tmux_context, "web");
+        let state = start(&state, &tmux_context, "worker");
+        assert!(fake.panes_beside("%0").is_empty());
+
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        assert_eq!(fake.panes_beside("%0"), vec![pane_id(&state, "web")]);
+
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        assert_eq!(fake.panes_beside("%0"), vec![pane_id(&state, "worker")]);
+        assert_eq!(
+            fake.pane(&pane_id(&state, "web")).unwrap().location,
+            PaneLocation::Window("$1".to_string())
+        );
+
+        focus_active_pane(&state, &tmux_context).unwrap();
+        assert_eq!(fake.selected_pane(), Some(pane_id(&state, "worker")));
+    }
+
+    #[test]
+    fn split_view_marks_the_selected_pane_without_focusing_it() {
+        let (state, tmux_context, fake) = setup();
+        let state = start(&state, &tmux_context, "web");
+        let state = start(&state, &tmux_context, "worker");
+        let gui_state = GUIStateMutation::on(&state.gui_state)
+            .set_split_view_proc_ids(vec![
+                process(&state, "web").id,
+                process(&state, "worker").id,
+            ])
+            .commit();
+        let state = StateMutation::on(&state).set_gui_state(gui_state).commit();
+        layout_split_view(&state, &tmux_context).unwrap();
+        let panes = vec![pane_id(&state, "web"), pane_id(&state, "worker")];
+        assert_eq!(fake.panes_beside("%0"), panes);
+
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        assert_eq!(fake.marked_pane(), Some(pane_id(&state, "web")));
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        assert_eq!(fake.marked_pane(), Some(pane_id(&state, "worker")));
+        assert_eq!(fake.panes_beside("%0"), panes);
+        assert_eq!(fake.selected_pane(), None);
+    }
+
+    #[test]
+    fn popped_out_panes_stay_put_while_navigating() {
+        let (state, tmux_context, fake) = setup();
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        let state = start(&state, &tmux_context, "web");
+        let state = start(&state, &tmux_context, "worker");
+        let web_pane_id = pane_id(&state, "web");
+        let popped_out = PaneLocation::Window("$0".to_string());
+
+        let state = toggle_pop_out(&state, &tmux_context).unwrap().unwrap();
+        assert_eq!(fake.pane(&web_pane_id).unwrap().location, popped_out);
+        assert!(fake.panes_beside("%0").is_empty());
+
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        assert_eq!(fake.panes_beside("%0"), vec![pane_id(&state, "worker")]);
+        let state = move_selection(&state, &tmux_context, -1).unwrap();
+        assert!(fake.panes_beside("%0").is_empty());
+        assert_eq!(fake.pane(&web_pane_id).unwrap().location, popped_out);
+
+        let state = toggle_pop_out(&state, &tmux_context).unwrap().unwrap();
+        assert!(state.gui_state.popped_out_proc_ids.is_empty());
+        assert_eq!(fake.panes_beside("%0"), vec![web_pane_id]);
+    }
+
+    #[test]
+    fn closing_a_popup_rejoins_the_pane() {
+        let (state, tmux_context, fake) = setup();
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        let state = start(&state, &tmux_context, "web");
+        let web = process(&state, "web").clone();
+
+        let popup = RefCell::new(None);
+        let state = open_popup(&state, &tmux_context, &popup).unwrap().unwrap();
+        let (process_id, mut child) = popup.into_inner().unwrap();
+        assert_eq!(process_id, web.id);
+        assert!(state.gui_state.popped_out_proc_ids.contains(&web.id));
+        assert_eq!(
+            fake.pane(&pane_id(&state, "web")).unwrap().location,
+            PaneLocation::Window("$1".to_string())
+        );
+
+        child.wait().unwrap();
+        let state = close_popup(&state, &tmux_context, process_id)
+            .unwrap()
+            .unwrap();
+        assert!(state.gui_state.popped_out_proc_ids.is_empty());
+        assert_eq!(fake.panes_beside("%0"), vec![pane_id(&state, "web")]);
+        // closing it again (e.g. after the pane was returned by hand) does nothing
+        assert!(close_popup(&state, &tmux_context, process_id)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn explains_popups_need_a_newer_tmux() {
+        let (state, tmux_context, fake) = setup();
+        fake.set_version(TmuxVersion { major: 3, minor: 1 });
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        let state = start(&state, &tmux_context, "web");
+
+        let popup = RefCell::new(None);
+        let state = open_popup(&state, &tmux_context, &popup).unwrap().unwrap();
+        assert!(popup.into_inner().is_none());
+        assert!(state.gui_state.popped_out_proc_ids.is_empty());
+        assert_eq!(
+            state.gui_state.messages.last().unwrap(),
+            "Popups require tmux 3.2 or later, found 3.1"
+        );
+        assert_eq!(fake.panes_beside("%0"), vec![pane_id(&state, "web")]);
+    }
+
+    #[test]
+    fn restarts_popped_out_processes_whose_pane_was_killed() {
+        let (state, tmux_context, fake) = setup();
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        let state = start(&state, &tmux_context, "web");
+        let state = toggle_pop_out(&state, &tmux_context).unwrap().unwrap();
+        fake.kill_pane(&pane_id(&state, "web")).unwrap();
+
+        // like on_reconcile
+        let closed = closed_panes(&state, &tmux_context.panes().unwrap());
+        assert_eq!(closed.len(), 1);
+        let state = forget_pane(&state, closed[0].id);
+        let state = set_process_terminated(&state, Some(&closed[0]), None).unwrap();
+        assert!(state.gui_state.popped_out_proc_ids.is_empty());
+
+        let web = process(&state, "web").clone();
+        let state = replace_pane_and_start_process(&state, &tmux_context, &hooks(), &web).unwrap();
+        assert_eq!(process(&state, "web").status, ProcessStatus::Running);
+        assert_eq!(fake.panes_beside("%0"), vec![pane_id(&state, "web")]);
+    }
+
+    #[test]
+    fn quitting_exits_once_every_process_is_halted() {
+        let (state, tmux_context, fake) = setup();
+        let state = start(&state, &tmux_context, "web");
+        let state = start(&state, &tmux_context, "worker");
+
+        let state = halt_all_and_exit(&state);
+        assert!(state
+            .processes
+            .iter()
+            .all(|p| p.status == ProcessStatus::Halting));
+        assert!(!is_exit_complete(&state));
+
+        let state = exit(&state, &fake, "web", None);
+        assert!(!is_exit_complete(&state));
+        let state = exit(&state, &fake, "worker", Some(0));
+        assert!(is_exit_complete(&state));
+
+        let panes = [pane_id(&state, "web"), pane_id(&state, "worker")];
+        let state = kill_halted_panes(&state, &tmux_context);
+        assert!(state.processes.iter().all(|p| p.pane_id.is_none()));
+        assert!(panes.iter().all(|pane_id| fake.pane(pane_id).is_none()));
+    }
+
+    #[test]
+    fn reconciles_processes_whose_exit_was_missed() {
+        let (state, tmux_context, fake) = setup();
+        let state = start(&state, &tmux_context, "web");
+        let pid = process(&state, "web").pid.unwrap();
+        let exited = |state: &State| exited_processes(state, &tmux_context.panes().unwrap());
+        assert!(exited(&state).is_empty());
+
+        // the pane dead event is lost, like while a control mode client restarts
+        fake.exit_pane(&pane_id(&state, "web"), Some(1));
+        assert_eq!(exited(&state), vec![(pid, Some(1))]);
+
+        let state = set_process_terminated(&state, state.get_process_by_pid(pid), Some(1)).unwrap();
+        assert!(exited(&state).is_empty());
+    }
+
+    #[test]
+    fn picks_up_exit_codes_once_tmux_reaps_the_process() {
+        let (state, tmux_context, fake) = setup();
+        let state = start(&state, &tmux_context, "web");
+        let web_pane_id = pane_id(&state, "web");
+        let reaped = |state: &State| reaped_exit_codes(state, &tmux_context.panes().unwrap());
+
+        // tmux lists the pane as dead before it has reaped the process
+        let state = exit(&state, &fake, "web", None);
+        assert_eq!(process(&state, "web").exit_code, None);
+        assert!(reaped(&state).is_empty());
+
+        fake.reap_pane(&web_pane_id, 3);
+        assert_eq!(reaped(&state), vec![(process(&state, "web").id, 3)]);
+    }
+
+    #[test]
+    fn forgets_panes_killed_outside_of_proctmux() {
+        let (state, tmux_context, fake) = setup();
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        let state = start(&state, &tmux_context, "web");
+        let state = start(&state, &tmux_context, "worker");
+        let web_pane_id = pane_id(&state, "web");
+        fake.kill_pane(&web_pane_id).unwrap();
+
+        // navigating away breaks the pane which is gone, and joins the next one
+        let state = move_selection(&state, &tmux_context, 1).unwrap();
+        assert_eq!(fake.panes_beside("%0"), vec![pane_id(&state, "worker")]);
+
+        let closed = closed_panes(&state, &tmux_context.panes().unwrap());
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].label, "web");
+        let state = forget_pane(&state, closed[0].id);
+        assert_eq!(process(&state, "web").pane_id, None);
+        assert!(closed_panes(&state, &tmux_context.panes().unwrap()).is_empty());
+
+        // halted processes whose pane is gone can be started again
+        let web = process(&state, "web").clone();
+        let state = set_process_terminated(&state, Some(&web), None).unwrap();
+        let web = process(&state, "web").clone();
+        let state = StateMutation::on(&state)
+            .set_process_pane_id(Some(web_pane_id), web.id)
+            .commit();
+        let web = process(&state, "web").clone();
+        let state = replace_pane_and_start_process(&state, &tmux_context, &hooks(), &web).unwrap();
+        assert_eq!(process(&state, "web").status, ProcessStatus::Running);
+    }
+
+    #[test]
+    fn starts_processes_once_their_before_start_hook_succeeds() {
+        let (mut state, tmux_context, fake) = setup();
+        let (sender, receiver) = channel();
+        let hooks = HookRunner::new(sender);
+        for p in state.processes.iter_mut().filter(|p| p.label == "web") {
+            p.config.before_start = Some("exit 0".to_string());
+        }
+        let web = process(&state, "web").clone();
+
+        let state = start_process(&state, &tmux_context, &hooks, &web)
+            .unwrap()
+            .unwrap();
+        let web = process(&state, "web").clone();
+        assert!(web.starting);
+        assert_eq!(web.pane_id, None);
+        // starting again while the hook runs does not run it twice
+        assert!(start_process(&state, &tmux_context, &hooks, &web)
+            .unwrap()
+            .is_none());
+
+        assert_eq!(
+            receiver.recv().unwrap(),
+            HookEvent::BeforeStartDone {
+                process_id: web.id,
+                error: None
+            }
+        );
+        let state = StateMutation::on(&state)
+            .set_process_starting(false, web.id)
+            .commit();
+        let state = create_process_pane(&state, &tmux_context, &hooks, &web).unwrap();
+        assert_eq!(process(&state, "web").status, ProcessStatus::Running);
+        assert!(fake.pane(&pane_id(&state, "web")).is_some());
+    }
+}
@@ -1,13 +1,113 @@
-use std::sync::{mpsc::Receiver, Arc, Mutex};
-use std::thread::spawn;
+use std::process::Child;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
 
 use crate::controller::Controller;
+use crate::hooks::HookEvent;
+use crate::resources::ResourceSampler;
+use crate::tmux_daemon::TmuxEvent;
 
-pub fn receive_dead_pids(receiver: Receiver<i32>, controller: Arc<Mutex<Controller>>) {
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(5);
+// how long to wait for more windows to close before reconciling
+const WINDOW_CLOSE_DELAY: Duration = Duration::from_secs(1);
+
+pub fn receive_tmux_events(receiver: Receiver<TmuxEvent>, controller: Arc<Mutex<Controller>>) {
+    spawn(move || {
+        // proctmux closes windows itself whenever it joins the only pane of one,
+        // e.g. on every navigation, so a burst of closes is reconciled once
+        let mut reconcile_at: Option<Instant> = None;
+        loop {
+            let event = match reconcile_at {
+                Some(at) => {
+                    match receiver.recv_timeout(at.saturating_duration_since(Instant::now())) {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => {
+                            reconcile_at = None;
+                            if let Err(e) = controller.lock().unwrap().on_reconcile() {
+                                error!("Error reconciling with tmux: {}", e);
+                            }
+                            continue;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                None => match receiver.recv() {
+                    Ok(event) => event,
+                    Err(_) => return,
+                },
+            };
+            match event {
+                TmuxEvent::PaneDead { pid, exit_code } => {
+                    trace!("Received dead pid: {}", pid);
+                    if let Err(e) = controller.lock().unwrap().on_pid_terminated(pid, exit_code) {
+                        error!("Error handling exit of pid {}: {}", pid, e);
+                    }
+                }
+                TmuxEvent::PaneOutput { pane_id, line } => {
+                    if let Err(e) = controller.lock().unwrap().on_pane_output(&pane_id, &line) {
+                        error!("Error handling output of pane {}: {}", pane_id, e);
+                    }
+                }
+                TmuxEvent::WindowClosed => {
+                    reconcile_at = Some(Instant::now() + WINDOW_CLOSE_DELAY);
+                }
+            }
+        }
+    });
+}
+
+pub fn receive_hook_events(receiver: Receiver<HookEvent>, controller: Arc<Mutex<Controller>>) {
+    spawn(move || {
+        for event in receiver {
+            let result = match event {
+                HookEvent::BeforeStartDone { process_id, error } => controller
+                    .lock()
+                    .unwrap()
+                    .on_before_start_done(process_id, error),
+                HookEvent::Failed(message) => {
+                    controller.lock().unwrap().on_error(Box::from(message))
+                }
+            };
+            if let Err(e) = result {
+                error!("Error handling hook completion: {}", e);
+            }
+        }
+    });
+}
+
+pub fn sample_resources(interval_ms: u64, controller: Arc<Mutex<Controller>>) {
     spawn(move || {
-        for pid in receiver {
-            trace!("Received dead pid: {}", pid);
-            controller.lock().unwrap().on_pid_terminated(pid).unwrap();
+        let mut sampler = ResourceSampler::new();
+        loop {
+            sleep(Duration::from_millis(interval_ms.max(100)));
+            // /proc is read without holding the controller
+            let pids = controller.lock().unwrap().process_pids();
+            let usages = sampler.sample(&pids);
+            if let Err(e) = controller.lock().unwrap().on_resource_sample(&usages) {
+                error!("Error sampling resource usage: {}", e);
+            }
+        }
+    });
+}
+
+pub fn reconcile(controller: Arc<Mutex<Controller>>) {
+    spawn(move || loop {
+        sleep(RECONCILE_INTERVAL);
+        if let Err(e) = controller.lock().unwrap().on_reconcile() {
+            error!("Error reconciling with tmux: {}", e);
+        }
+    });
+}
+
+pub fn wait_for_popup(mut popup: Child, process_id: usize, controller: Arc<Mutex<Controller>>) {
+    spawn(move || {
+        if let Err(e) = popup.wait() {
+            error!("Error waiting for popup: {}", e);
+        }
+        if let Err(e) = controller.lock().unwrap().on_popup_closed(process_id) {
+            error!("Error returning pane after popup closed: {}", e);
         }
     });
 }
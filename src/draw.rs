@@ -7,12 +7,18 @@ use termion::color::{self, Bg, Color, Fg};
 use termion::raw::{IntoRawMode, RawTerminal};
 use termion::{clear, cursor, style, terminal_size};
 
+use crate::constants::{CATEGORY_INDENT, COLLAPSED, EXPANDED};
 use crate::frame::{
     break_at_natural_break_points, wrap_lines_to_width, wrap_to_width, ColoredSegment,
     Partitionable, ProcessPanelFrame,
 };
-use crate::repr::{color_from_config_string, get_status_arrow_and_color, keybinding_help};
-use crate::state::State;
+use crate::process::Process;
+use crate::repr::{
+    color_from_config_string, format_duration, get_alert_badge,
+    get_category_status_arrow_and_color, get_mark, get_process_columns, get_status_arrow_and_color,
+    get_status_name, get_task_duration, get_usage_summary, keybinding_help,
+};
+use crate::state::{ListEntry, State};
 
 pub fn init_screen() -> Result<RawTerminal<Stdout>, Box<dyn Error>> {
     let mut stdout = stdout().into_raw_mode()?;
@@ -37,8 +43,55 @@ pub fn get_help_messages(state: &State) -> Vec<ColoredSegment> {
     msg.push(keybinding_help(keybindings.quit.as_slice(), "quit"));
     msg.push(keybinding_help(keybindings.start.as_slice(), "start"));
     msg.push(keybinding_help(keybindings.stop.as_slice(), "stop"));
+    msg.push(keybinding_help(keybindings.restart.as_slice(), "restart"));
+    msg.push(keybinding_help(
+        keybindings.start_all.as_slice(),
+        "start all",
+    ));
+    msg.push(keybinding_help(keybindings.stop_all.as_slice(), "stop all"));
+    msg.push(keybinding_help(
+        keybindings.restart_all.as_slice(),
+        "restart all",
+    ));
+    if !state.config.groups.is_empty() {
+        msg.push(keybinding_help(
+            keybindings.start_group.as_slice(),
+            "start group",
+        ));
+        msg.push(keybinding_help(
+            keybindings.stop_group.as_slice(),
+            "stop group",
+        ));
+        msg.push(keybinding_help(
+            keybindings.restart_group.as_slice(),
+            "restart group",
+        ));
+    }
     msg.push(keybinding_help(keybindings.up.as_slice(), "up"));
     msg.push(keybinding_help(keybindings.down.as_slice(), "down"));
+    msg.push(keybinding_help(
+        keybindings.toggle_details.as_slice(),
+        "details",
+    ));
+    msg.push(keybinding_help(
+        keybindings.toggle_split_view.as_slice(),
+        "split view",
+    ));
+    msg.push(keybinding_help(keybindings.pop_out.as_slice(), "pop out"));
+    msg.push(keybinding_help(keybindings.popup.as_slice(), "popup"));
+    msg.push(keybinding_help(
+        keybindings.grow_pane.as_slice(),
+        "grow pane",
+    ));
+    msg.push(keybinding_help(
+        keybindings.shrink_pane.as_slice(),
+        "shrink pane",
+    ));
+    msg.push(keybinding_help(keybindings.move_up.as_slice(), "move up"));
+    msg.push(keybinding_help(
+        keybindings.move_down.as_slice(),
+        "move down",
+    ));
     msg.push(keybinding_help(keybindings.filter.as_slice(), "filter"));
     msg.push(keybinding_help(
         keybindings.filter_submit.as_slice(),
@@ -84,42 +137,189 @@ fn get_process_lines(state: &State) -> (Vec<Vec<ColoredSegment>>, Option<usize>)
     let process_label_width = state.config.layout.process_list_width - 3;
     let mut current_process_line_index = None;
     let mut lines: Vec<Vec<ColoredSegment>> = vec![];
-    for proc in state.get_filtered_processes().iter() {
+    let current_entry = state.current_entry();
+    let filter_matches = state.get_filter_matches();
+    let filtered_procs: Vec<&Process> = filter_matches.iter().map(|(p, _)| *p).collect();
+    for entry in state.list_entries(&filtered_procs).iter() {
+        let proc = match entry {
+            ListEntry::Category(category) => {
+                let selected = *entry == current_entry;
+                lines.push(get_category_line(
+                    state,
+                    &state.category_processes(&filtered_procs, category),
+                    category,
+                    selected,
+                    process_label_width,
+                ));
+                if selected {
+                    current_process_line_index = Some(lines.len() - 1);
+                }
+                continue;
+            }
+            ListEntry::Process(process_id) => match state.get_process(*process_id) {
+                Some(proc) => proc,
+                None => continue,
+            },
+        };
+        let indent =
+            if state.config.layout.group_by_category && state.process_category(proc).is_some() {
+                CATEGORY_INDENT
+            } else {
+                ""
+            };
+        let suffixes: Vec<ColoredSegment> = get_task_duration(proc)
+            .into_iter()
+            .chain(get_process_columns(state, proc))
+            .chain(get_alert_badge(state, proc))
+            .collect();
+        let suffix_width: usize = suffixes.iter().map(|s| s.text.chars().count()).sum();
+        let matched_positions = filter_matches
+            .iter()
+            .find(|(p, _)| p.id == proc.id)
+            .map(|(_, m)| m.positions.as_slice())
+            .unwrap_or_default();
         if state.current_proc_id == proc.id {
-            let bg = color_from_config_string(&state.config.style.selected_process_bg_color)
-                .unwrap_or(Box::new(color::LightMagenta));
-            let fg = color_from_config_string(&state.config.style.selected_process_color)
-                .unwrap_or(Box::new(color::Black));
-            let line = vec![
-                get_status_arrow_and_color(state, proc),
+            let mut line = vec![
                 ColoredSegment::new_basic(
                     Box::new(color::White) as Box<dyn Color>,
-                    " ".to_string(),
+                    indent.to_string(),
                 ),
-                ColoredSegment::new_basic(fg as Box<dyn Color>, proc.label.clone())
-                    .set_bg(bg)
-                    .set_style(Box::new(style::Bold) as Box<dyn Display>)
-                    .set_width(process_label_width),
+                get_status_arrow_and_color(state, proc),
+                get_mark(state, proc),
             ];
+            line.extend(get_label_segments(
+                proc,
+                matched_positions,
+                &|| {
+                    color_from_config_string(&state.config.style.selected_process_color)
+                        .unwrap_or(Box::new(color::Black))
+                },
+                Some(&|| {
+                    color_from_config_string(&state.config.style.selected_process_bg_color)
+                        .unwrap_or(Box::new(color::LightMagenta))
+                }),
+                Some(process_label_width.saturating_sub(suffix_width + indent.len())),
+            ));
+            line.extend(suffixes);
             lines.push(line);
             current_process_line_index = Some(lines.len() - 1);
         } else {
-            let fg = color_from_config_string(&state.config.style.unselected_process_color)
-                .unwrap_or(Box::new(color::Cyan));
-            let line = vec![
-                get_status_arrow_and_color(state, proc),
+            let mut line = vec![
                 ColoredSegment::new_basic(
                     Box::new(color::White) as Box<dyn Color>,
-                    " ".to_string(),
+                    indent.to_string(),
                 ),
-                ColoredSegment::new_basic(fg as Box<dyn Color>, proc.label.clone()),
+                get_status_arrow_and_color(state, proc),
+                get_mark(state, proc),
             ];
+            line.extend(get_label_segments(
+                proc,
+                matched_positions,
+                &|| {
+                    color_from_config_string(&state.config.style.unselected_process_color)
+                        .unwrap_or(Box::new(color::Cyan))
+                },
+                None,
+                // right-align suffixes
+                (!suffixes.is_empty())
+                    .then(|| process_label_width.saturating_sub(suffix_width + indent.len())),
+            ));
+            line.extend(suffixes);
             lines.push(line);
         }
     }
     (lines, current_process_line_index)
 }
 
+/*
+ * The process label, split so the characters matched by the filter
+ * text (at positions) are underlined. A background is only given to
+ * selected labels, and a width pads the label out to the edge of the
+ * process list.
+ */
+fn get_label_segments(
+    proc: &Process,
+    positions: &[usize],
+    fg: &dyn Fn() -> Box<dyn Color>,
+    bg: Option<&dyn Fn() -> Box<dyn Color>>,
+    width: Option<usize>,
+) -> Vec<ColoredSegment> {
+    let mut runs: Vec<(bool, String)> = vec![];
+    for (idx, c) in proc.label.chars().enumerate() {
+        let matched = positions.contains(&idx);
+        match runs.last_mut() {
+            Some((m, text)) if *m == matched => text.push(c),
+            _ => runs.push((matched, c.to_string())),
+        }
+    }
+    if runs.is_empty() {
+        runs.push((false, String::new()));
+    }
+
+    let run_count = runs.len();
+    let mut used_width = 0;
+    runs.into_iter()
+        .enumerate()
+        .map(|(idx, (matched, text))| {
+            let text_width = text.chars().count();
+            let mut segment = ColoredSegment::new_basic(fg(), text);
+            if let Some(bg) = bg {
+                segment = segment.set_bg(bg());
+            }
+            let style = match (matched, bg.is_some()) {
+                (true, true) => Some(format!("{}{}", style::Bold, style::Underline)),
+                (true, false) => Some(style::Underline.to_string()),
+                (false, true) => Some(style::Bold.to_string()),
+                (false, false) => None,
+            };
+            if let Some(style) = style {
+                segment = segment.set_style(Box::new(style) as Box<dyn Display>);
+            }
+            if let Some(width) = width {
+                if idx == run_count - 1 {
+                    segment = segment.set_width(width.saturating_sub(used_width));
+                }
+            }
+            used_width += text_width;
+            segment
+        })
+        .collect()
+}
+
+fn get_category_line(
+    state: &State,
+    processes: &[&Process],
+    category: &str,
+    selected: bool,
+    label_width: usize,
+) -> Vec<ColoredSegment> {
+    let collapsed = state.gui_state.collapsed_categories.contains(category);
+    let text = format!(
+        "{} {} ({})",
+        if collapsed { COLLAPSED } else { EXPANDED },
+        category,
+        processes.len()
+    );
+    let label = if selected {
+        let bg = color_from_config_string(&state.config.style.selected_process_bg_color)
+            .unwrap_or(Box::new(color::LightMagenta));
+        let fg = color_from_config_string(&state.config.style.selected_process_color)
+            .unwrap_or(Box::new(color::Black));
+        ColoredSegment::new_basic(fg, text)
+            .set_bg(bg)
+            .set_style(Box::new(style::Bold) as Box<dyn Display>)
+            .set_width(label_width)
+    } else {
+        ColoredSegment::new_basic(Box::new(color::White) as Box<dyn Color>, text)
+            .set_style(Box::new(style::Bold) as Box<dyn Display>)
+    };
+    vec![
+        get_category_status_arrow_and_color(state, processes),
+        ColoredSegment::new_basic(Box::new(color::White) as Box<dyn Color>, " ".to_string()),
+        label,
+    ]
+}
+
 fn get_message_lines(state: &State) -> Vec<ColoredSegment> {
     let process_list_width = state.config.layout.process_list_width;
     let current_proc = state.current_process();
@@ -138,6 +338,12 @@ fn get_message_lines(state: &State) -> Vec<ColoredSegment> {
                     ));
                 }
             }
+            if let Some(usage) = get_usage_summary(current_proc) {
+                all_msgs.push(ColoredSegment::new_basic(
+                    Box::new(color::White) as Box<dyn Color>,
+                    usage,
+                ));
+            }
         }
     }
 
@@ -152,12 +358,76 @@ fn get_message_lines(state: &State) -> Vec<ColoredSegment> {
 
     all_msgs
 }
+
+/*
+ * How the selected process was configured and has run, shown below
+ * the process list when toggled on
+ */
+fn get_details_lines(state: &State) -> Vec<ColoredSegment> {
+    let process = match state.current_process() {
+        Some(process) if state.gui_state.show_details => process,
+        _ => return vec![],
+    };
+    let width = state.config.layout.process_list_width;
+    let mut lines = vec![
+        "─".repeat(width),
+        format!("command: {}", process.command()),
+        format!("cwd: {}", process.config.cwd),
+    ];
+    if let Some(add_path) = &process.config.add_path {
+        lines.push(format!("add_path: {}", add_path.join(":")));
+    }
+    let mut env: Vec<_> = process.config.env.iter().flatten().collect();
+    env.sort_by(|a, b| a.0.cmp(b.0));
+    for (k, v) in env {
+        lines.push(format!("env: {}={}", k, v.as_deref().unwrap_or("")));
+    }
+    lines.push(format!(
+        "pane: {}  pid: {}",
+        process.pane_id.as_deref().unwrap_or("-"),
+        process.pid.map_or("-".to_string(), |pid| pid.to_string())
+    ));
+    lines.push(format!(
+        "status: {}  last exit: {}",
+        get_status_name(&process.status),
+        process
+            .last_exit_code
+            .map_or("-".to_string(), |code| code.to_string())
+    ));
+    if let Some(exited_at) = process.exited_at {
+        lines.push(format!(
+            "exited {} ago",
+            format_duration(exited_at.elapsed())
+        ));
+    }
+    if let Some(usage) = get_usage_summary(process) {
+        lines.push(usage);
+    }
+    for change in process.status_history.iter().rev() {
+        let exit_code = change
+            .exit_code
+            .map_or(String::new(), |code| format!(" ({})", code));
+        lines.push(format!(
+            "  {} ago: {}{}",
+            format_duration(change.at.elapsed()),
+            get_status_name(&change.status),
+            exit_code
+        ));
+    }
+    lines
+        .iter()
+        .flat_map(|line| wrap_to_width(width, line))
+        .map(|line| ColoredSegment::new_basic(Box::new(color::White) as Box<dyn Color>, line))
+        .collect()
+}
+
 pub fn construct_frame(state: &State) -> ProcessPanelFrame {
     let mut frame = ProcessPanelFrame::new(state.config.layout.process_list_width);
     frame.set_filter_line(get_filter_frame_line(state));
     let (proc_lines, current_idx) = get_process_lines(state);
     frame.set_process_lines(proc_lines);
     frame.set_current_process_line_index(current_idx);
+    frame.set_details(get_details_lines(state));
     frame.set_messages(get_message_lines(state));
     frame
 }
@@ -215,6 +485,7 @@ fn draw_frame(mut stdout: &Stdout, frame: &ProcessPanelFrame) -> Result<(), Box<
                 process_iter = frame.process_lines[..end_idx].iter();
             }
         }
+        let process_partition_top = y_offset;
         for line in process_iter {
             goto_from_top(stdout, y_offset)?;
 
@@ -223,6 +494,14 @@ fn draw_frame(mut stdout: &Stdout, frame: &ProcessPanelFrame) -> Result<(), Box<
             }
             y_offset += 1;
         }
+        y_offset = process_partition_top + process_partition.height;
+        let details_partition = partition_iter.next().unwrap();
+        let end_idx = min(frame.details.len(), details_partition.height as usize);
+        for seg in frame.details[..end_idx].iter() {
+            goto_from_top(stdout, y_offset)?;
+            draw_colored_segment(stdout, seg)?;
+            y_offset += 1;
+        }
         let message_partition = partition_iter.next().unwrap();
         let mut messages_iter = frame.messages.iter();
         let end_idx = min(frame.messages.len(), message_partition.height as usize);
@@ -0,0 +1,360 @@
+/*
+    An in-memory TmuxBackend for tests, keeping track of which panes
+    exist, where they are and whether their process has exited
+*/
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::process::{Child, Command};
+use std::sync::{Arc, Mutex};
+
+use crate::config::SidebarPosition;
+use crate::tmux::TmuxVersion;
+use crate::tmux_backend::{Env, PaneNotFound, PaneState, TmuxBackend};
+use crate::tmux_daemon::TmuxEvent;
+
+// beyond the largest pid linux hands out, so signals sent while halting go nowhere
+const FIRST_PID: i32 = 1 << 30;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaneLocation {
+    // joined to the window of the given (sidebar) pane
+    Beside(String),
+    // in its own window of the given session
+    Window(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct FakePane {
+    pub pid: i32,
+    pub location: PaneLocation,
+    pub command: Option<String>,
+    pub remain_on_exit: bool,
+    pub dead: bool,
+    pub exit_code: Option<i32>,
+}
+
+#[derive(Default)]
+struct FakeServer {
+    panes: BTreeMap<String, FakePane>,
+    pane_count: usize,
+    pid_count: i32,
+    selected_pane: Option<String>,
+    marked_pane: Option<String>,
+    // the latest version unless set (see set_version)
+    version: Option<TmuxVersion>,
+}
+
+impl FakeServer {
+    fn add_pane(&mut self, location: PaneLocation, command: Option<&str>) -> String {
+        self.pane_count += 1;
+        self.pid_count += 1;
+        let pane_id = format!("%{}", self.pane_count);
+        self.panes.insert(
+            pane_id.clone(),
+            FakePane {
+                pid: FIRST_PID + self.pid_count,
+                location,
+                command: command.map(|c| c.to_string()),
+                remain_on_exit: false,
+                dead: false,
+                exit_code: None,
+            },
+        );
+        pane_id
+    }
+
+    fn pane(&mut self, pane_id: &str) -> Result<&mut FakePane, Box<dyn Error>> {
+        self.panes
+            .get_mut(pane_id)
+            .ok_or_else(|| Box::from(PaneNotFound(format!("can't find pane: {}", pane_id))))
+    }
+}
+
+/*
+ * Clones share the same panes, so a test can keep a clone
+ * to inspect the panes of the backend it hands to a TmuxContext
+ */
+#[derive(Clone, Default)]
+pub struct FakeTmux {
+    server: Arc<Mutex<FakeServer>>,
+}
+
+impl FakeTmux {
+    /*
+     * A server with the sidebar pane (%0) in session $0
+     */
+    pub fn new() -> Self {
+        let fake = Self::default();
+        fake.server.lock().unwrap().panes.insert(
+            "%0".to_string(),
+            FakePane {
+                pid: FIRST_PID,
+                location: PaneLocation::Window("$0".to_string()),
+                command: None,
+                remain_on_exit: false,
+                dead: false,
+                exit_code: None,
+            },
+        );
+        fake
+    }
+
+    pub fn pane(&self, pane_id: &str) -> Option<FakePane> {
+        self.server.lock().unwrap().panes.get(pane_id).cloned()
+    }
+
+    /*
+     * Ids of the panes joined beside the given (sidebar) pane
+     */
+    pub fn panes_beside(&self, pane_id: &str) -> Vec<String> {
+        let location = PaneLocation::Beside(pane_id.to_string());
+        self.server
+            .lock()
+            .unwrap()
+            .panes
+            .iter()
+            .filter(|(_, pane)| pane.location == location)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    pub fn selected_pane(&self) -> Option<String> {
+        self.server.lock().unwrap().selected_pane.clone()
+    }
+
+    pub fn marked_pane(&self) -> Option<String> {
+        self.server.lock().unwrap().marked_pane.clone()
+    }
+
+    /*
+     * Simulates the process of the pane exiting, returning the event the
+     * control mode subscription would deliver. Without remain-on-exit the
+     * pane is closed, like tmux does.
+     */
+    pub fn exit_pane(&self, pane_id: &str, exit_code: Option<i32>) -> Option<TmuxEvent> {
+        let mut server = self.server.lock().unwrap();
+        let pane = server.panes.get_mut(pane_id)?;
+        if pane.dead {
+            return None;
+        }
+        pane.dead = true;
+        pane.exit_code = exit_code;
+        let pid = pane.pid;
+        if !pane.remain_on_exit {
+            server.panes.remove(pane_id);
+        }
+        Some(TmuxEvent::PaneDead { pid, exit_code })
+    }
+
+    /*
+     * tmux reaping the process of a dead pane after reporting the pane dead
+     */
+    pub fn reap_pane(&self, pane_id: &str, exit_code: i32) {
+        if let Some(pane) = self.server.lock().unwrap().panes.get_mut(pane_id) {
+            pane.exit_code = Some(exit_code);
+        }
+    }
+
+    pub fn set_version(&self, version: TmuxVersion) {
+        self.server.lock().unwrap().version = Some(version);
+    }
+}
+
+impl TmuxBackend for FakeTmux {
+    fn create_pane(
+        &self,
+        target_pane: &str,
+        command: &str,
+        _working_directory: &str,
+        _env: &Env,
+        _position: SidebarPosition,
+        _size: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut server = self.server.lock().unwrap();
+        // remain-on-exit is a window option, so new panes get the setting of their window
+        let remain_on_exit = server.pane(target_pane)?.remain_on_exit;
+        let pane_id = server.add_pane(PaneLocation::Beside(target_pane.to_string()), Some(command));
+        server.pane(&pane_id)?.remain_on_exit = remain_on_exit;
+        Ok(pane_id)
+    }
+
+    fn create_detached_pane(
+        &self,
+        session: &str,
+        _window: usize,
+        _window_label: &str,
+        _working_directory: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut server = self.server.lock().unwrap();
+        Ok(server.add_pane(PaneLocation::Window(session.to_string()), None))
+    }
+
+    fn respawn_pane(
+        &self,
+        pane_id: &str,
+        command: &str,
+        _working_directory: &str,
+        _env: &Env,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut server = self.server.lock().unwrap();
+        server.pid_count += 1;
+        let pid = FIRST_PID + server.pid_count;
+        let pane = server.pane(pane_id)?;
+        pane.pid = pid;
+        pane.command = Some(command.to_string());
+        pane.dead = false;
+        pane.exit_code = None;
+        Ok(())
+    }
+
+    fn set_remain_on_exit(&self, pane_id: &str, on: bool) -> Result<(), Box<dyn Error>> {
+        self.server.lock().unwrap().pane(pane_id)?.remain_on_exit = on;
+        Ok(())
+    }
+
+    fn break_pane(
+        &self,
+        pane_id: &str,
+        session: &str,
+        _window: usize,
+        _window_label: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.server.lock().unwrap().pane(pane_id)?.location =
+            PaneLocation::Window(session.to_string());
+        Ok(())
+    }
+
+    fn break_pane_to_window(
+        &self,
+        pane_id: &str,
+        session: &str,
+        _window_label: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.break_pane(pane_id, session, 0, "")
+    }
+
+    fn join_pane(
+        &self,
+        pane_id: &str,
+        dest_pane: &str,
+        _position: SidebarPosition,
+        _size: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut server = self.server.lock().unwrap();
+        server.pane(dest_pane)?;
+        server.pane(pane_id)?.location = PaneLocation::Beside(dest_pane.to_string());
+        Ok(())
+    }
+
+    fn join_pane_beside(
+        &self,
+        pane_id: &str,
+        dest_pane: &str,
+        _horizontal: bool,
+        _size: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut server = self.server.lock().unwrap();
+        let location = server.pane(dest_pane)?.location.clone();
+        server.pane(pane_id)?.location = location;
+        Ok(())
+    }
+
+    fn kill_pane(&self, pane_id: &str) -> Result<(), Box<dyn Error>> {
+        let mut server = self.server.lock().unwrap();
+        server.pane(pane_id)?;
+        server.panes.remove(pane_id);
+        Ok(())
+    }
+
+    fn kill_session(&self, session: &str) -> Result<(), Box<dyn Error>> {
+        let location = PaneLocation::Window(session.to_string());
+        self.server
+            .lock()
+            .unwrap()
+            .panes
+            .retain(|_, pane| pane.location != location);
+        Ok(())
+    }
+
+    fn select_pane(&self, pane_id: &str) -> Result<(), Box<dyn Error>> {
+        let mut server = self.server.lock().unwrap();
+        server.pane(pane_id)?;
+        server.selected_pane = Some(pane_id.to_string());
+        Ok(())
+    }
+
+    fn mark_pane(&self, target_pane: &str, pane_id: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let mut server = self.server.lock().unwrap();
+        server.pane(target_pane)?;
+        server.marked_pane = None;
+        if let Some(pane_id) = pane_id {
+            server.pane(pane_id)?;
+            server.marked_pane = Some(pane_id.to_string());
+        }
+        Ok(())
+    }
+
+    fn pane_pid(&self, pane_id: &str) -> Result<i32, Box<dyn Error>> {
+        Ok(self.server.lock().unwrap().pane(pane_id)?.pid)
+    }
+
+    fn panes(&self, session: &str) -> Result<Vec<PaneState>, Box<dyn Error>> {
+        let server = self.server.lock().unwrap();
+        let in_session = |pane: &FakePane| match &pane.location {
+            PaneLocation::Window(s) => s == session,
+            PaneLocation::Beside(pane_id) => server
+                .panes
+                .get(pane_id)
+                .is_some_and(|p| p.location == PaneLocation::Window(session.to_string())),
+        };
+        Ok(server
+            .panes
+            .iter()
+            .filter(|(_, pane)| in_session(pane))
+            .map(|(pane_id, pane)| PaneState {
+                pane_id: pane_id.clone(),
+                dead: pane.dead.then_some((pane.pid, pane.exit_code)),
+            })
+            .collect())
+    }
+
+    fn resize_pane(
+        &self,
+        pane_id: &str,
+        _direction: &str,
+        _amount: u16,
+    ) -> Result<(), Box<dyn Error>> {
+        self.server.lock().unwrap().pane(pane_id)?;
+        Ok(())
+    }
+
+    fn pane_format(&self, pane_id: &str, _format: &str) -> Result<String, Box<dyn Error>> {
+        self.server.lock().unwrap().pane(pane_id)?;
+        Ok("".to_string())
+    }
+
+    fn toggle_zoom(&self, _pane_id: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn display_message(&self, _target_pane: &str, _message: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn popup_window(
+        &self,
+        _target_pane: &str,
+        _session: &str,
+        _window: usize,
+        _title: &str,
+    ) -> Result<Child, Box<dyn Error>> {
+        Ok(Command::new("true").spawn()?)
+    }
+
+    fn version(&self) -> TmuxVersion {
+        self.server.lock().unwrap().version.unwrap_or(TmuxVersion {
+            major: u32::MAX,
+            minor: u32::MAX,
+        })
+    }
+}
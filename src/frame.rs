@@ -5,9 +5,11 @@ use termion::color::Color;
 use crate::constants::MIN_SCREEN_HEIGHT;
 
 pub struct ProcessPanelFrame {
+    #[allow(dead_code)]
     pub width: usize,
     pub filter_line: Option<Vec<ColoredSegment>>,
     pub process_lines: Vec<Vec<ColoredSegment>>,
+    pub details: Vec<ColoredSegment>,
     pub messages: Vec<ColoredSegment>,
     pub current_process_line_index: Option<usize>,
 }
@@ -18,6 +20,7 @@ impl ProcessPanelFrame {
             width,
             filter_line: None,
             process_lines: vec![],
+            details: vec![],
             messages: vec![],
             current_process_line_index: None,
         }
@@ -37,6 +40,10 @@ impl ProcessPanelFrame {
         self.current_process_line_index = current_process_line_index;
         self
     }
+    pub fn set_details(&mut self, details: Vec<ColoredSegment>) -> &mut Self {
+        self.details = details;
+        self
+    }
     pub fn set_messages(&mut self, messages: Vec<ColoredSegment>) -> &mut Self {
         self.messages = messages;
         self
@@ -72,22 +79,38 @@ impl Partitionable for ProcessPanelFrame {
             });
             remaining_height -= filter_line_height;
         }
-        if self.process_lines.len() + self.messages.len() <= remaining_height as usize {
-            partitions.push(Partition {
-                height: self.process_lines.len() as u16,
-                fits: true,
-            });
-            partitions.push(Partition {
-                height: self.messages.len() as u16,
-                fits: true,
-            });
+        // process lines, details and messages, in order from top to bottom
+        let sections = [
+            self.process_lines.len(),
+            self.details.len(),
+            self.messages.len(),
+        ];
+        if sections.iter().sum::<usize>() <= remaining_height as usize {
+            for len in sections.iter() {
+                partitions.push(Partition {
+                    height: *len as u16,
+                    fits: true,
+                });
+            }
         } else {
-            let process_partition_height = (remaining_height as usize * 75 / 100) as u16;
+            let (process_percentage, details_percentage) = if self.details.is_empty() {
+                (75, 0)
+            } else {
+                (45, 35)
+            };
+            let process_partition_height =
+                (remaining_height as usize * process_percentage / 100) as u16;
+            let details_partition_height =
+                (remaining_height as usize * details_percentage / 100) as u16;
             partitions.push(Partition {
                 height: process_partition_height,
                 fits: self.process_lines.len() <= process_partition_height as usize,
             });
-            remaining_height -= process_partition_height;
+            partitions.push(Partition {
+                height: details_partition_height,
+                fits: self.details.len() <= details_partition_height as usize,
+            });
+            remaining_height -= process_partition_height + details_partition_height;
 
             partitions.push(Partition {
                 height: remaining_height,
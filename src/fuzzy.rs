@@ -0,0 +1,168 @@
+/*
+    fzf style fuzzy matching of filter text against processes
+*/
+use crate::process::Process;
+
+const SCORE_MATCH: i32 = 16;
+const BONUS_CONSECUTIVE: i32 = 8;
+const BONUS_BOUNDARY: i32 = 8;
+const BONUS_FIRST_CHAR: i32 = 10;
+const BONUS_CAMEL_CASE: i32 = 7;
+const PENALTY_GAP: i32 = 1;
+const MAX_GAP_PENALTY: i32 = 8;
+
+#[derive(Debug, PartialEq)]
+pub struct FuzzyMatch {
+    pub score: i32,
+    // char indices of the matched characters
+    pub positions: Vec<usize>,
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn is_boundary(prev: char) -> bool {
+    !prev.is_alphanumeric()
+}
+
+/*
+ * Matches the characters of pattern, in order, case-insensitively against text.
+ * The shortest window of text containing the match is scored, favoring
+ * consecutive characters and characters at the start of words.
+ */
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = pattern.chars().map(lowercase).collect();
+    if pattern.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: vec![],
+        });
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| lowercase(*c)).collect();
+
+    // find the end of the first occurrence of the pattern
+    let mut pattern_idx = 0;
+    let mut end = None;
+    for (idx, c) in lower.iter().enumerate() {
+        if *c == pattern[pattern_idx] {
+            pattern_idx += 1;
+            if pattern_idx == pattern.len() {
+                end = Some(idx);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // walk backwards from the end to find the shortest window
+    let mut start = end;
+    let mut pattern_idx = pattern.len();
+    for idx in (0..=end).rev() {
+        if lower[idx] == pattern[pattern_idx - 1] {
+            pattern_idx -= 1;
+            if pattern_idx == 0 {
+                start = idx;
+                break;
+            }
+        }
+    }
+
+    let mut positions = vec![];
+    let mut pattern_idx = 0;
+    for (idx, c) in lower.iter().enumerate().take(end + 1).skip(start) {
+        if pattern_idx < pattern.len() && *c == pattern[pattern_idx] {
+            positions.push(idx);
+            pattern_idx += 1;
+        }
+    }
+
+    let mut score = 0;
+    let mut prev_pos: Option<usize> = None;
+    for pos in positions.iter() {
+        score += SCORE_MATCH;
+        if *pos == 0 {
+            score += BONUS_FIRST_CHAR;
+        } else if is_boundary(chars[pos - 1]) {
+            score += BONUS_BOUNDARY;
+        } else if chars[pos - 1].is_lowercase() && chars[*pos].is_uppercase() {
+            score += BONUS_CAMEL_CASE;
+        }
+        if let Some(prev) = prev_pos {
+            if *pos == prev + 1 {
+                score += BONUS_CONSECUTIVE;
+            } else {
+                score -= ((pos - prev - 1) as i32 * PENALTY_GAP).min(MAX_GAP_PENALTY);
+            }
+        }
+        prev_pos = Some(*pos);
+    }
+
+    Some(FuzzyMatch { score, positions })
+}
+
+/*
+ * Each whitespace separated term of the filter text must match the label,
+ * description, a meta tag or a category of the process. Label matches score
+ * higher and are the only ones with positions (for highlighting).
+ */
+pub fn fuzzy_match_process(filter_text: &str, process: &Process) -> Option<FuzzyMatch> {
+    let mut others: Vec<&String> = vec![];
+    others.extend(process.config.description.iter());
+    others.extend(process.config.meta_tags.iter().flatten());
+    others.extend(process.config.categories.iter().flatten());
+
+    let mut score = 0;
+    let mut positions = vec![];
+    for term in filter_text.split_whitespace() {
+        let label_match = fuzzy_match(term, &process.label);
+        let other_score = others
+            .iter()
+            .filter_map(|s| fuzzy_match(term, s))
+            .map(|m| m.score / 2)
+            .max();
+        match (label_match, other_score) {
+            (Some(m), Some(other)) if other > m.score => score += other,
+            (Some(m), _) => {
+                score += m.score;
+                positions.extend(m.positions);
+            }
+            (None, Some(other)) => score += other,
+            (None, None) => return None,
+        }
+    }
+    positions.sort();
+    positions.dedup();
+    Some(FuzzyMatch { score, positions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_match_finds_shortest_window() {
+        let m = fuzzy_match("api", "frontend-app-api").unwrap();
+        assert_eq!(m.positions, vec![13, 14, 15]);
+        assert!(fuzzy_match("xyz", "frontend-app-api").is_none());
+        assert!(fuzzy_match("pa", "api").is_none());
+    }
+
+    #[test]
+    fn fuzzy_match_prefers_word_starts_and_consecutive_chars() {
+        let boundary = fuzzy_match("bw", "backend/worker").unwrap();
+        let middle = fuzzy_match("bw", "cobweb").unwrap();
+        assert!(boundary.score > middle.score);
+
+        let consecutive = fuzzy_match("work", "backend/worker").unwrap();
+        let scattered = fuzzy_match("work", "wide-order-kit").unwrap();
+        assert!(consecutive.score > scattered.score);
+    }
+
+    #[test]
+    fn fuzzy_match_is_case_insensitive() {
+        let m = fuzzy_match("ApI", "apiServer").unwrap();
+        assert_eq!(m.positions, vec![0, 1, 2]);
+    }
+}
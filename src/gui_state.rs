@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use crate::state::Mutator;
 
 #[derive(Clone, Debug)]
@@ -5,6 +7,16 @@ pub struct GUIState {
     pub messages: Vec<String>,
     pub filter_text: Option<String>,
     pub entering_filter_text: bool,
+    pub marked_proc_ids: HashSet<usize>,
+    pub selected_category: Option<String>,
+    pub collapsed_categories: HashSet<String>,
+    pub show_details: bool,
+    // starts as layout.process_pane_size, updated when the pane is resized
+    pub process_pane_size: String,
+    // processes whose panes are joined side by side, empty unless in split view
+    pub split_view_proc_ids: Vec<usize>,
+    // processes whose panes are shown in their own window or a popup
+    pub popped_out_proc_ids: HashSet<usize>,
 }
 
 pub struct GUIStateMutation {
@@ -39,11 +51,62 @@ impl GUIStateMutation {
         self
     }
 
+    pub fn toggle_mark(mut self, process_id: usize) -> Self {
+        if !self.init_state.marked_proc_ids.remove(&process_id) {
+            self.init_state.marked_proc_ids.insert(process_id);
+        }
+        self
+    }
+
+    pub fn clear_marks(mut self) -> Self {
+        self.init_state.marked_proc_ids.clear();
+        self
+    }
+
+    pub fn set_selected_category(mut self, category: Option<String>) -> Self {
+        self.init_state.selected_category = category;
+        self
+    }
+
+    pub fn toggle_collapsed_category(mut self, category: &str) -> Self {
+        if !self.init_state.collapsed_categories.remove(category) {
+            self.init_state
+                .collapsed_categories
+                .insert(category.to_string());
+        }
+        self
+    }
+
+    pub fn set_process_pane_size(mut self, size: String) -> Self {
+        self.init_state.process_pane_size = size;
+        self
+    }
+
+    pub fn set_popped_out(mut self, process_id: usize, popped_out: bool) -> Self {
+        if popped_out {
+            self.init_state.popped_out_proc_ids.insert(process_id);
+        } else {
+            self.init_state.popped_out_proc_ids.remove(&process_id);
+        }
+        self
+    }
+
+    pub fn set_split_view_proc_ids(mut self, process_ids: Vec<usize>) -> Self {
+        self.init_state.split_view_proc_ids = process_ids;
+        self
+    }
+
+    pub fn toggle_details(mut self) -> Self {
+        self.init_state.show_details = !self.init_state.show_details;
+        self
+    }
+
     pub fn add_message(mut self, message: String) -> Self {
         self.init_state.messages.push(message);
         self
     }
 
+    #[allow(dead_code)]
     pub fn clear_messages(mut self) -> Self {
         self.init_state.messages.clear();
         self
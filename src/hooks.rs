@@ -0,0 +1,182 @@
+use std::error::Error;
+use std::process::Command;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread::{spawn, JoinHandle};
+
+use crate::process::Process;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hook {
+    BeforeStart,
+    AfterStart,
+    AfterStop,
+    OnFail,
+}
+
+impl Hook {
+    fn name(&self) -> &'static str {
+        match self {
+            Hook::BeforeStart => "before_start",
+            Hook::AfterStart => "after_start",
+            Hook::AfterStop => "after_stop",
+            Hook::OnFail => "on_fail",
+        }
+    }
+
+    pub fn command<'a>(&self, process: &'a Process) -> Option<&'a String> {
+        match self {
+            Hook::BeforeStart => process.config.before_start.as_ref(),
+            Hook::AfterStart => process.config.after_start.as_ref(),
+            Hook::AfterStop => process.config.after_stop.as_ref(),
+            Hook::OnFail => process.config.on_fail.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum HookEvent {
+    // the before_start hook of a process completed, with the failure if it failed
+    BeforeStartDone {
+        process_id: usize,
+        error: Option<String>,
+    },
+    Failed(String),
+}
+
+/*
+ * Runs hooks on their own threads, so slow hooks do not hold up input,
+ * drawing or the handling of exited processes. Completions and failures
+ * are sent as HookEvents (see daemon::receive_hook_events).
+ */
+#[derive(Clone)]
+pub struct HookRunner {
+    sender: Sender<HookEvent>,
+    threads: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl HookRunner {
+    pub fn new(sender: Sender<HookEvent>) -> Self {
+        HookRunner {
+            sender,
+            threads: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /*
+     * Runs the hooks of the process one after the other
+     */
+    pub fn run(&self, hooks: &[Hook], process: &Process, pid: Option<i32>, exit_code: Option<i32>) {
+        let hooks: Vec<Hook> = hooks
+            .iter()
+            .filter(|hook| hook.command(process).is_some())
+            .copied()
+            .collect();
+        if hooks.is_empty() {
+            return;
+        }
+        let process = process.clone();
+        let sender = self.sender.clone();
+        self.spawn(move || {
+            for hook in hooks {
+                if let Err(e) = run_hook(hook, &process, pid, exit_code) {
+                    error!("{}", e);
+                    sender.send(HookEvent::Failed(e.to_string())).unwrap_or(());
+                }
+            }
+        });
+    }
+
+    pub fn run_before_start(&self, process: &Process) {
+        let process = process.clone();
+        let sender = self.sender.clone();
+        self.spawn(move || {
+            let error = run_hook(Hook::BeforeStart, &process, None, None)
+                .err()
+                .map(|e| e.to_string());
+            sender
+                .send(HookEvent::BeforeStartDone {
+                    process_id: process.id,
+                    error,
+                })
+                .unwrap_or(());
+        });
+    }
+
+    /*
+     * Waits for the hooks still running, e.g. the after_stop hooks run while quitting
+     */
+    pub fn wait(&self) {
+        let threads: Vec<JoinHandle<()>> = self.threads.lock().unwrap().drain(..).collect();
+        for thread in threads {
+            thread.join().unwrap_or(());
+        }
+    }
+
+    fn spawn<F: FnOnce() + Send + 'static>(&self, f: F) {
+        let mut threads = self.threads.lock().unwrap();
+        threads.retain(|thread| !thread.is_finished());
+        threads.push(spawn(f));
+    }
+}
+
+/*
+ * Runs a process' hook command (if it has one) to completion, outside of tmux,
+ * in the process' working directory and environment.
+ * Returns an error describing the failure if the command does not succeed.
+ */
+fn run_hook(
+    hook: Hook,
+    process: &Process,
+    pid: Option<i32>,
+    exit_code: Option<i32>,
+) -> Result<(), Box<dyn Error>> {
+    let command = match hook.command(process) {
+        Some(command) => command,
+        None => return Ok(()),
+    };
+    info!(
+        "Running {} hook for {}: {}",
+        hook.name(),
+        process.label,
+        command
+    );
+
+    let mut c = Command::new("sh");
+    c.arg("-c")
+        .arg(command)
+        .current_dir(&process.config.cwd)
+        .env("PROCTMUX_PROCESS", &process.label);
+    if let Some(env) = &process.config.env {
+        for (k, v) in env.iter() {
+            c.env(k, v.clone().unwrap_or_default());
+        }
+    }
+    if let Some(pid) = pid {
+        c.env("PROCTMUX_PID", pid.to_string());
+    }
+    if let Some(exit_code) = exit_code {
+        c.env("PROCTMUX_EXIT_CODE", exit_code.to_string());
+    }
+
+    let output = c
+        .output()
+        .map_err(|e| format!("{} hook for {} failed: {}", hook.name(), process.label, e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = stderr
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .map(|line| line.trim().to_string())
+        .unwrap_or(output.status.to_string());
+    Err(Box::from(format!(
+        "{} hook for {} failed: {}",
+        hook.name(),
+        process.label,
+        reason
+    )))
+}
@@ -8,6 +8,7 @@ use termion::{event::Key, input::TermRead};
 
 use crate::config::KeybindingConfig;
 use crate::controller::Controller;
+use crate::daemon::wait_for_popup;
 
 pub fn input_loop(
     controller: Arc<Mutex<Controller>>,
@@ -95,10 +96,7 @@ fn handle_filter_entry_keypresses(
         }
     } else if let Key::Char(c) = key {
         let filter_text = controller.lock().unwrap().filter_text();
-        let mut new_filter_text = match filter_text {
-            Some(filter_text) => filter_text,
-            None => String::new(),
-        };
+        let mut new_filter_text = filter_text.unwrap_or_default();
         new_filter_text.push(c);
         info!("setting filter text: {:?}", new_filter_text);
         controller
@@ -124,6 +122,45 @@ fn handle_normal_mode_keypresses(
         controller.lock().unwrap().on_keypress_start()?;
     } else if keybinding.stop.contains(&key) {
         controller.lock().unwrap().on_keypress_stop()?;
+    } else if keybinding.restart.contains(&key) {
+        controller.lock().unwrap().on_keypress_restart()?;
+    } else if keybinding.start_group.contains(&key) {
+        controller.lock().unwrap().on_keypress_start_group()?;
+    } else if keybinding.stop_group.contains(&key) {
+        controller.lock().unwrap().on_keypress_stop_group()?;
+    } else if keybinding.restart_group.contains(&key) {
+        controller.lock().unwrap().on_keypress_restart_group()?;
+    } else if keybinding.mark.contains(&key) {
+        controller.lock().unwrap().on_keypress_mark()?;
+    } else if keybinding.clear_marks.contains(&key) {
+        controller.lock().unwrap().on_keypress_clear_marks()?;
+    } else if keybinding.toggle_category.contains(&key) {
+        controller.lock().unwrap().on_keypress_toggle_category()?;
+    } else if keybinding.toggle_details.contains(&key) {
+        controller.lock().unwrap().on_keypress_toggle_details()?;
+    } else if keybinding.toggle_split_view.contains(&key) {
+        controller.lock().unwrap().on_keypress_toggle_split_view()?;
+    } else if keybinding.pop_out.contains(&key) {
+        controller.lock().unwrap().on_keypress_pop_out()?;
+    } else if keybinding.popup.contains(&key) {
+        let popup = controller.lock().unwrap().on_keypress_popup()?;
+        if let Some((process_id, child)) = popup {
+            wait_for_popup(child, process_id, controller.clone());
+        }
+    } else if keybinding.grow_pane.contains(&key) {
+        controller.lock().unwrap().on_keypress_grow_pane()?;
+    } else if keybinding.shrink_pane.contains(&key) {
+        controller.lock().unwrap().on_keypress_shrink_pane()?;
+    } else if keybinding.move_up.contains(&key) {
+        controller.lock().unwrap().on_keypress_move_up()?;
+    } else if keybinding.move_down.contains(&key) {
+        controller.lock().unwrap().on_keypress_move_down()?;
+    } else if keybinding.start_all.contains(&key) {
+        controller.lock().unwrap().on_keypress_start_all()?;
+    } else if keybinding.stop_all.contains(&key) {
+        controller.lock().unwrap().on_keypress_stop_all()?;
+    } else if keybinding.restart_all.contains(&key) {
+        controller.lock().unwrap().on_keypress_restart_all()?;
     } else if keybinding.filter.contains(&key) {
         controller.lock().unwrap().on_filter_start()?;
     } else if keybinding.switch_focus.contains(&key) {
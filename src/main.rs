@@ -1,16 +1,27 @@
+mod alert;
 mod args;
 mod config;
 mod constants;
+mod control_api;
 mod controller;
 mod daemon;
 mod draw;
+#[cfg(test)]
+mod fake_tmux;
 mod frame;
+mod fuzzy;
 mod gui_state;
+mod hooks;
 mod input;
+mod notification;
+mod order;
 mod process;
+mod query;
 mod repr;
+mod resources;
 mod state;
 mod tmux;
+mod tmux_backend;
 mod tmux_context;
 mod tmux_daemon;
 
@@ -21,10 +32,12 @@ use std::sync::{Arc, Mutex};
 
 use args::parse_config_from_args;
 use controller::Controller;
-use daemon::receive_dead_pids;
+use daemon::{receive_hook_events, receive_tmux_events, reconcile, sample_resources};
+use hooks::HookRunner;
 use input::input_loop;
 use state::State;
-use tmux_context::TmuxContext;
+use tmux::{TmuxServer, TmuxVersion};
+use tmux_context::{is_inside_server, run_in_new_session, TmuxContext};
 use tmux_daemon::TmuxDaemon;
 
 #[macro_use]
@@ -32,6 +45,25 @@ extern crate log;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let config = parse_config_from_args()?;
+    let tmux_server = TmuxServer::new(
+        &config.general.tmux_bin,
+        config.general.tmux_socket.as_deref(),
+    );
+    let tmux_version = tmux::version(&tmux_server)?;
+    let tmux_server = tmux_server.with_version(tmux_version);
+    if !tmux_version.is_supported() {
+        return Err(Box::from(format!(
+            "proctmux requires tmux {} or later, found {}",
+            TmuxVersion::MINIMUM,
+            tmux_version
+        )));
+    }
+
+    // outside of the configured tmux server (or of tmux altogether),
+    // proctmux starts again inside a new session of it
+    if !is_inside_server(&tmux_server) {
+        return run_in_new_session(&tmux_server);
+    }
 
     let file = std::fs::File::create(config.log_file.clone()).unwrap();
     env_logger::builder()
@@ -39,61 +71,61 @@ fn main() -> Result<(), Box<dyn Error>> {
         .filter_level(log::LevelFilter::Debug)
         .init();
 
-    info!("Starting proctmux");
+    info!("Starting proctmux (tmux {})", tmux_version);
+    if !tmux_version.supports_subscriptions() {
+        warn!(
+            "tmux {} has no subscriptions, polling for exited processes instead",
+            tmux_version
+        );
+    }
 
     let tmux_context = TmuxContext::new(
+        tmux_server.clone(),
         &config.general.detached_session_name,
         config.general.kill_existing_session,
     )?;
 
     let running = Arc::new(AtomicBool::new(true));
-    let mut tmux_daemon_attached = TmuxDaemon::new(&tmux_context.session_id)?;
-    let mut tmux_daemon_detached = TmuxDaemon::new(&tmux_context.detached_session_id)?;
+    let mut tmux_daemon_attached = TmuxDaemon::new(&tmux_server, &tmux_context.session_id)?;
+    let mut tmux_daemon_detached =
+        TmuxDaemon::new(&tmux_server, &tmux_context.detached_session_id)?;
     let state = State::new(&config);
+    let (hook_sender, hook_receiver) = channel();
     let controller = Arc::new(Mutex::new(Controller::new(
         state,
         tmux_context,
+        HookRunner::new(hook_sender),
         running.clone(),
     )?));
     let (sender, receiver) = channel();
 
-    receive_dead_pids(receiver, controller.clone());
-
-    /*
-    * Creating TmuxDaemon instances (which start tmux processes in control mode) as
-    * soon as possible, and then waiting as long as we can to call the
-    * listen_for_dead_panes method on each instance (which sends the refresh-client
-    * command to the tmux control mode process of the TmuxDaemon instance),
-    * is necessary to make sure the refresh-client command succeeds on all
-    * machines/environments.
-
-    * When refresh-client is called with -B argument, tmux checks that the
-    * CLIENT_CONTROL bit is set. If it isn't the "not a control client"
-    * error appears and the subscription is not created. The CLIENT_CONTROL
-    * bit should be set when tmux is started with -C argument (control mode).
-
-    * On some machines/environments, the "not a control client" error manifests if
-    * listen_for_dead_panes is called too soon after a TmuxDaemon is instantiated.
-    * My best guess is this is a timing bug in tmux. Maybe we're calling refresh-client
-    * too soon after starting tmux, and the CLIENT_CONTROL bit hasn't been set
-    * yet. After enough time has passed (a few milliseconds? maybe not even one?) the
-    * refresh-client command will succeed, indicating the CLIENT_CONTROL bit is set.
-
-    * If a tmux bug is identified/fixed this timing should no longer be a concern,
-    * listen_for_dead_panes should be callable immediately after instantiating a TmuxDaemon.
-    */
-    std::thread::sleep(std::time::Duration::from_millis(5));
-    tmux_daemon_attached.listen_for_dead_panes(sender.clone())?;
-    tmux_daemon_detached.listen_for_dead_panes(sender)?;
+    receive_tmux_events(receiver, controller.clone());
+    receive_hook_events(hook_receiver, controller.clone());
+    reconcile(controller.clone());
+    if config.resources_enabled() {
+        sample_resources(config.resources.interval_ms, controller.clone());
+    }
+
+    tmux_daemon_attached.listen(sender.clone())?;
+    tmux_daemon_detached.listen(sender)?;
+    tmux_server.set_control_client(Some(tmux_daemon_attached.control_client()));
 
     controller.lock().unwrap().on_startup()?;
+    if let Some(path) = &config.general.control_socket {
+        control_api::listen(path, controller.clone())?;
+    }
     input_loop(controller.clone(), config.keybinding, running);
 
     info!("Exiting proctmux");
 
+    tmux_server.set_control_client(None);
+
     tmux_daemon_attached.kill()?;
     tmux_daemon_detached.kill()?;
     controller.lock().unwrap().on_exit();
+    if let Some(path) = &config.general.control_socket {
+        std::fs::remove_file(path).unwrap_or(());
+    }
 
     Ok(())
 }
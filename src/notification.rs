@@ -0,0 +1,77 @@
+use std::io::{Stdout, Write};
+use std::process::{Command, Stdio};
+use std::thread::spawn;
+
+use crate::config::{NotificationConfig, NotificationEvent};
+use crate::process::Process;
+use crate::tmux_context::TmuxContext;
+
+fn event_name(event: NotificationEvent) -> &'static str {
+    match event {
+        NotificationEvent::Exit => "exit",
+        NotificationEvent::Fail => "fail",
+        NotificationEvent::Ready => "ready",
+        NotificationEvent::Alert => "alert",
+        NotificationEvent::Usage => "usage",
+    }
+}
+
+/*
+ * Runs the user defined notification command without blocking,
+ * the child is reaped on a separate thread
+ */
+fn run_notification_command(
+    command: &str,
+    event: NotificationEvent,
+    process: &Process,
+    message: &str,
+) -> std::io::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("PROCTMUX_EVENT", event_name(event))
+        .env("PROCTMUX_PROCESS", &process.label)
+        .env("PROCTMUX_MESSAGE", message)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    spawn(move || {
+        if let Err(e) = child.wait() {
+            error!("Error waiting for notification command: {}", e);
+        }
+    });
+    Ok(())
+}
+
+pub fn notify(
+    config: &NotificationConfig,
+    mut stdout: &Stdout,
+    tmux_context: &TmuxContext,
+    event: NotificationEvent,
+    process: &Process,
+    message: &str,
+) {
+    if !config.events.contains(&event) {
+        return;
+    }
+    info!("Notification ({}): {}", event_name(event), message);
+
+    if config.tmux_message {
+        if let Err(e) = tmux_context.display_message(message) {
+            error!("Error displaying notification message: {}", e);
+        }
+    }
+
+    if config.bell {
+        if let Err(e) = write!(stdout, "\x07").and_then(|_| stdout.flush()) {
+            error!("Error ringing terminal bell: {}", e);
+        }
+    }
+
+    if let Some(command) = &config.command {
+        if let Err(e) = run_notification_command(command, event, process, message) {
+            error!("Error running notification command '{}': {}", command, e);
+        }
+    }
+}
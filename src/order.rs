@@ -0,0 +1,106 @@
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+
+use crate::process::Process;
+
+/*
+ * Labels of the processes in the order they were last arranged,
+ * empty if the order was never saved
+ */
+pub fn load_order(order_file: &str) -> Vec<String> {
+    match fs::read_to_string(order_file) {
+        Ok(contents) => contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.to_string())
+            .collect(),
+        Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+        Err(e) => {
+            error!("Error reading process order from {}: {}", order_file, e);
+            vec![]
+        }
+    }
+}
+
+pub fn save_order(order_file: &str, processes: &[Process]) -> Result<(), Box<dyn Error>> {
+    let contents: String = processes.iter().map(|p| format!("{}\n", p.label)).collect();
+    fs::write(order_file, contents)
+        .map_err(|e| format!("Error saving process order to {}: {}", order_file, e))?;
+    Ok(())
+}
+
+/*
+ * Orders processes (already in config file or alphabetical order) with
+ * pinned processes first, then by their order field. The saved order only
+ * arranges processes sharing the same pinned and order values, so editing
+ * order in the config file always takes effect.
+ * The sort is stable, so processes missing from the saved order keep their
+ * previous relative order.
+ */
+pub fn sort_processes(processes: &mut [Process], saved_order: &[String]) {
+    processes.sort_by_key(|p| {
+        let order = match p.config.order {
+            Some(order) => (0, order),
+            None => (1, 0),
+        };
+        let saved_position = saved_order
+            .iter()
+            .position(|label| *label == p.label)
+            .unwrap_or(saved_order.len());
+        (!p.config.pinned, order, saved_position)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProcessConfig;
+
+    fn process(id: usize, label: &str, order: Option<i64>, pinned: bool) -> Process {
+        let mut config: ProcessConfig = serde_yaml::from_str("shell: \"true\"").unwrap();
+        config.order = order;
+        config.pinned = pinned;
+        Process::new(id, label, config)
+    }
+
+    fn labels(processes: &[Process]) -> Vec<&str> {
+        processes.iter().map(|p| p.label.as_str()).collect()
+    }
+
+    #[test]
+    fn sort_processes_applies_order_and_pins() {
+        let mut processes = vec![
+            process(1, "a", None, false),
+            process(2, "b", Some(2), false),
+            process(3, "c", Some(1), false),
+            process(4, "d", None, true),
+        ];
+        sort_processes(&mut processes, &[]);
+        assert_eq!(labels(&processes), vec!["d", "c", "b", "a"]);
+
+        let saved = vec!["a".to_string(), "b".to_string(), "d".to_string()];
+        sort_processes(&mut processes, &saved);
+        assert_eq!(labels(&processes), vec!["d", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn sort_processes_uses_saved_order_within_the_same_order_value() {
+        let mut processes = vec![
+            process(1, "a", Some(1), false),
+            process(2, "b", Some(1), false),
+            process(3, "c", Some(2), false),
+            process(4, "d", None, false),
+            process(5, "e", None, false),
+        ];
+        let saved = vec![
+            "c".to_string(),
+            "e".to_string(),
+            "b".to_string(),
+            "d".to_string(),
+            "a".to_string(),
+        ];
+        sort_processes(&mut processes, &saved);
+        assert_eq!(labels(&processes), vec!["b", "a", "c", "e", "d"]);
+    }
+}
@@ -1,4 +1,10 @@
-use crate::config::ProcessConfig;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::alert::{compile_alert_rules, compile_ready_rule, AlertRule};
+use crate::config::{AlertLevel, ProcessConfig};
+use crate::resources::ResourceUsage;
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum ProcessStatus {
@@ -7,13 +13,36 @@ pub enum ProcessStatus {
     Halted = 3,
 }
 
+#[derive(Clone, Debug)]
+pub struct StatusChange {
+    pub at: Instant,
+    pub status: ProcessStatus,
+    pub exit_code: Option<i32>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Process {
     pub id: usize,
     pub label: String,
     pub status: ProcessStatus,
+    pub status_history: Vec<StatusChange>,
     pub pane_id: Option<String>,
     pub pid: Option<i32>,
+    pub exit_code: Option<i32>,
+    pub last_exit_code: Option<i32>,
+    pub started_at: Option<Instant>,
+    pub exited_at: Option<Instant>,
+    pub run_duration: Option<Duration>,
+    pub restart_count: usize,
+    pub usage: Option<ResourceUsage>,
+    pub restart_pending: bool,
+    // the before_start hook is running, the process starts once it succeeds
+    pub starting: bool,
+    pub ready: bool,
+    pub alert: Option<AlertLevel>,
+    pub unread_alerts: usize,
+    pub alert_rules: Vec<AlertRule>,
+    pub ready_rule: Option<Regex>,
     pub config: ProcessConfig,
 }
 
@@ -23,8 +52,23 @@ impl Process {
             id,
             label: label.to_string(),
             status: ProcessStatus::Halted,
+            status_history: vec![],
             pane_id: None,
             pid: None,
+            exit_code: None,
+            last_exit_code: None,
+            started_at: None,
+            exited_at: None,
+            run_duration: None,
+            restart_count: 0,
+            usage: None,
+            restart_pending: false,
+            starting: false,
+            ready: false,
+            alert: None,
+            unread_alerts: 0,
+            alert_rules: compile_alert_rules(label, &config.alerts),
+            ready_rule: compile_ready_rule(label, &config.ready_pattern),
             config,
         }
     }
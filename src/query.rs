@@ -0,0 +1,249 @@
+/*
+    Filter text query language:
+    - whitespace separated terms must all match
+    - '|' separates alternatives, any of which may match
+    - a leading '!' negates a term
+    - prefixed terms (status:, tag:, cat:, group:) match process attributes,
+      other terms are fuzzy matched
+*/
+use crate::config::{LayoutConfig, ProcTmuxConfig};
+use crate::fuzzy::{fuzzy_match_process, FuzzyMatch};
+use crate::process::{Process, ProcessStatus};
+
+#[derive(Debug, PartialEq)]
+enum Term {
+    Status(String),
+    Tag(String),
+    Category(String),
+    Group(String),
+    Text(String),
+}
+
+#[derive(Debug, PartialEq)]
+struct Atom {
+    negated: bool,
+    term: Term,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Query {
+    alternatives: Vec<Vec<Atom>>,
+}
+
+fn parse_term(text: &str, layout: &LayoutConfig) -> Term {
+    if let Some(value) = text.strip_prefix(&layout.status_search_prefix) {
+        return Term::Status(value.to_lowercase());
+    }
+    if let Some(value) = text.strip_prefix(&layout.tag_search_prefix) {
+        return Term::Tag(value.to_lowercase());
+    }
+    if let Some(value) = text.strip_prefix(&layout.category_search_prefix) {
+        return Term::Category(value.to_lowercase());
+    }
+    if let Some(value) = text.strip_prefix(&layout.group_search_prefix) {
+        return Term::Group(value.to_string());
+    }
+    Term::Text(text.to_string())
+}
+
+fn status_matches(status: &str, process: &Process) -> bool {
+    match status {
+        "running" => process.status == ProcessStatus::Running,
+        "halting" | "stopping" => process.status == ProcessStatus::Halting,
+        "halted" | "stopped" => process.status == ProcessStatus::Halted,
+        "failed" => {
+            process.status == ProcessStatus::Halted && process.exit_code.is_some_and(|c| c != 0)
+        }
+        "succeeded" => process.status == ProcessStatus::Halted && process.exit_code == Some(0),
+        "ready" => process.ready,
+        _ => false,
+    }
+}
+
+fn contains_ignore_case(values: &Option<Vec<String>>, value: &str) -> bool {
+    values.iter().flatten().any(|v| v.to_lowercase() == value)
+}
+
+impl Term {
+    fn evaluate(&self, config: &ProcTmuxConfig, process: &Process) -> Option<FuzzyMatch> {
+        let matched = match self {
+            Term::Status(status) => status_matches(status, process),
+            Term::Tag(tag) => contains_ignore_case(&process.config.meta_tags, tag),
+            Term::Category(category) => contains_ignore_case(&process.config.categories, category),
+            Term::Group(group) => config
+                .groups
+                .get(group)
+                .is_some_and(|labels| labels.contains(&process.label)),
+            Term::Text(text) => return fuzzy_match_process(text, process),
+        };
+        matched.then(|| FuzzyMatch {
+            score: 0,
+            positions: vec![],
+        })
+    }
+}
+
+impl Query {
+    pub fn parse(text: &str, layout: &LayoutConfig) -> Self {
+        let alternatives = text
+            .split('|')
+            .map(|alternative| {
+                alternative
+                    .split_whitespace()
+                    .filter_map(|word| {
+                        let (negated, word) = match word.strip_prefix('!') {
+                            Some(word) => (true, word),
+                            None => (false, word),
+                        };
+                        if word.is_empty() {
+                            return None;
+                        }
+                        Some(Atom {
+                            negated,
+                            term: parse_term(word, layout),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|atoms| !atoms.is_empty())
+            .collect();
+        Query { alternatives }
+    }
+
+    /*
+     * The group every alternative filters by, e.g. web for "group:web status:running"
+     */
+    pub fn group(&self) -> Option<&str> {
+        let groups: Vec<Option<&str>> = self
+            .alternatives
+            .iter()
+            .map(|atoms| {
+                atoms.iter().find_map(|atom| match &atom.term {
+                    Term::Group(group) if !atom.negated => Some(group.as_str()),
+                    _ => None,
+                })
+            })
+            .collect();
+        let group = (*groups.first()?)?;
+        groups.iter().all(|g| *g == Some(group)).then_some(group)
+    }
+
+    /*
+     * The best scoring alternative matching the process, with the positions
+     * of the label characters matched by its (non negated) text terms
+     */
+    pub fn evaluate(&self, config: &ProcTmuxConfig, process: &Process) -> Option<FuzzyMatch> {
+        if self.alternatives.is_empty() {
+            return Some(FuzzyMatch {
+                score: 0,
+                positions: vec![],
+            });
+        }
+        self.alternatives
+            .iter()
+            .filter_map(|atoms| {
+                let mut score = 0;
+                let mut positions = vec![];
+                for atom in atoms.iter() {
+                    let result = atom.term.evaluate(config, process);
+                    match (atom.negated, result) {
+                        (false, Some(m)) => {
+                            score += m.score;
+                            positions.extend(m.positions);
+                        }
+                        (true, None) => {}
+                        _ => return None,
+                    }
+                }
+                positions.sort();
+                positions.dedup();
+                Some(FuzzyMatch { score, positions })
+            })
+            .max_by_key(|m| m.score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> ProcTmuxConfig {
+        serde_yaml::from_str(
+            r#"
+log_file: /dev/null
+keybinding: {}
+groups:
+  web: [frontend]
+procs:
+  frontend:
+    shell: "true"
+    categories: [web]
+    meta_tags: [Node]
+  backend-api:
+    shell: "true"
+    categories: [backend]
+  backend-worker:
+    shell: "true"
+    categories: [backend, slow]
+"#,
+        )
+        .unwrap()
+    }
+
+    fn matching_labels(query: &str, processes: &[Process], config: &ProcTmuxConfig) -> Vec<String> {
+        let query = Query::parse(query, &config.layout);
+        let mut labels: Vec<String> = processes
+            .iter()
+            .filter(|p| query.evaluate(config, p).is_some())
+            .map(|p| p.label.clone())
+            .collect();
+        labels.sort();
+        labels
+    }
+
+    #[test]
+    fn query_combines_and_or_and_negation() {
+        let config = test_config();
+        let mut processes: Vec<Process> = config
+            .procs
+            .iter()
+            .enumerate()
+            .map(|(id, (label, cfg))| Process::new(id, label, cfg.clone()))
+            .collect();
+        for process in processes.iter_mut() {
+            if process.label == "backend-worker" {
+                process.exit_code = Some(1);
+            }
+        }
+
+        assert_eq!(
+            matching_labels("cat:backend status:failed", &processes, &config),
+            vec!["backend-worker"]
+        );
+        assert_eq!(
+            matching_labels("cat:backend !cat:slow", &processes, &config),
+            vec!["backend-api"]
+        );
+        assert_eq!(
+            matching_labels("tag:node | group:web | api", &processes, &config),
+            vec!["backend-api", "frontend"]
+        );
+        assert_eq!(
+            matching_labels("!bknd", &processes, &config),
+            vec!["frontend"]
+        );
+        assert_eq!(matching_labels(" | ", &processes, &config).len(), 3);
+    }
+
+    #[test]
+    fn query_names_the_group_it_filters_by() {
+        let config = test_config();
+        let group = |text| Query::parse(text, &config.layout).group().map(String::from);
+        assert_eq!(group("group:web"), Some("web".to_string()));
+        assert_eq!(group("status:running group:web"), Some("web".to_string()));
+        assert_eq!(group("group:web | group:web api"), Some("web".to_string()));
+        assert_eq!(group("group:web | api"), None);
+        assert_eq!(group("!group:web"), None);
+        assert_eq!(group("web"), None);
+    }
+}
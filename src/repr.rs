@@ -2,12 +2,15 @@
     A module that provides handy representation / conversion fucntions
 */
 use crate::{
-    constants::{ANSI_PREFIX, DOWN, UP},
+    config::{AlertLevel, ProcessColumn, ProcessKind},
+    constants::{ALERT_BADGE, ANSI_PREFIX, DOWN, FAILURE, RESTARTS, SUCCESS, UP},
     frame::ColoredSegment,
     process::{Process, ProcessStatus},
+    resources::{exceeds_cpu_threshold, exceeds_memory_threshold, format_bytes},
     state::State,
 };
 use std::error::Error;
+use std::time::Duration;
 use termion::{
     color::{self, Color},
     event::Key,
@@ -24,12 +27,183 @@ pub fn get_status_arrow_and_color(state: &State, process: &Process) -> ColoredSe
                 .unwrap_or(Box::new(color::Yellow));
             ColoredSegment::new_basic(fg, format!(" {}", DOWN))
         }
-        ProcessStatus::Halted => {
-            let fg = color_from_config_string(&state.config.style.status_stopped_color)
-                .unwrap_or(Box::new(color::Red));
-            ColoredSegment::new_basic(fg, format!(" {}", DOWN))
-        }
+        ProcessStatus::Halted => match (process.config.kind, process.exit_code) {
+            (ProcessKind::Task, Some(0)) => {
+                let fg = color_from_config_string(&state.config.style.status_success_color)
+                    .unwrap_or(Box::new(color::Green));
+                ColoredSegment::new_basic(fg, format!(" {}", SUCCESS))
+            }
+            (ProcessKind::Task, _) if process.run_duration.is_some() => {
+                let fg = color_from_config_string(&state.config.style.status_failure_color)
+                    .unwrap_or(Box::new(color::Red));
+                ColoredSegment::new_basic(fg, format!(" {}", FAILURE))
+            }
+            _ => {
+                let fg = color_from_config_string(&state.config.style.status_stopped_color)
+                    .unwrap_or(Box::new(color::Red));
+                ColoredSegment::new_basic(fg, format!(" {}", DOWN))
+            }
+        },
+    }
+}
+
+pub fn get_status_name(status: &ProcessStatus) -> &'static str {
+    match status {
+        ProcessStatus::Running => "running",
+        ProcessStatus::Halting => "halting",
+        ProcessStatus::Halted => "halted",
+    }
+}
+
+/*
+ * Running if every process of a category is running, halting (partially running)
+ * if only some of them are, and halted if none are
+ */
+pub fn get_category_status_arrow_and_color(
+    state: &State,
+    processes: &[&Process],
+) -> ColoredSegment {
+    let running = processes
+        .iter()
+        .filter(|p| p.status == ProcessStatus::Running)
+        .count();
+    if running > 0 && running == processes.len() {
+        let fg = color_from_config_string(&state.config.style.status_running_color)
+            .unwrap_or(Box::new(color::Green));
+        ColoredSegment::new_basic(fg, format!(" {}", UP))
+    } else if running > 0 {
+        let fg = color_from_config_string(&state.config.style.status_halting_color)
+            .unwrap_or(Box::new(color::Yellow));
+        ColoredSegment::new_basic(fg, format!(" {}", UP))
+    } else {
+        let fg = color_from_config_string(&state.config.style.status_stopped_color)
+            .unwrap_or(Box::new(color::Red));
+        ColoredSegment::new_basic(fg, format!(" {}", DOWN))
+    }
+}
+
+/*
+ * The separator between the status and label of a process,
+ * showing whether the process is marked
+ */
+pub fn get_mark(state: &State, process: &Process) -> ColoredSegment {
+    if !state.gui_state.marked_proc_ids.contains(&process.id) {
+        return ColoredSegment::new_basic(Box::new(color::White), " ".to_string());
+    }
+    let fg = color_from_config_string(&state.config.style.marked_process_color)
+        .unwrap_or(Box::new(color::Yellow));
+    let mark = state.config.style.mark_char.chars().next().unwrap_or('*');
+    ColoredSegment::new_basic(fg, mark.to_string())
+}
+
+/*
+ * How long the last run of a completed task took
+ */
+pub fn get_task_duration(process: &Process) -> Option<ColoredSegment> {
+    if process.config.kind != ProcessKind::Task || process.status != ProcessStatus::Halted {
+        return None;
     }
+    let duration = process.run_duration?;
+    Some(ColoredSegment::new_basic(
+        Box::new(color::LightBlack),
+        format!(" {}", format_duration(duration)),
+    ))
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h{}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else if secs >= 10 {
+        format!("{}s", secs)
+    } else {
+        format!("{:.1}s", duration.as_secs_f32())
+    }
+}
+
+pub fn get_alert_badge(state: &State, process: &Process) -> Option<ColoredSegment> {
+    let level = process.alert?;
+    let (color_str, default_color): (&str, Box<dyn Color>) = match level {
+        AlertLevel::Error => (&state.config.style.alert_error_color, Box::new(color::Red)),
+        AlertLevel::Warn => (
+            &state.config.style.alert_warn_color,
+            Box::new(color::Yellow),
+        ),
+        AlertLevel::Info => (&state.config.style.alert_info_color, Box::new(color::Cyan)),
+    };
+    let fg = color_from_config_string(color_str).unwrap_or(default_color);
+    Some(ColoredSegment::new_basic(
+        fg,
+        format!(" {}{}", ALERT_BADGE, process.unread_alerts),
+    ))
+}
+
+/*
+ * The configured columns (layout.process_list_columns) of a process,
+ * columns without a value for the process are left out.
+ * Usage over a configured threshold is colored as a warning.
+ */
+pub fn get_process_columns(state: &State, process: &Process) -> Vec<ColoredSegment> {
+    let resources = &state.config.resources;
+    state
+        .config
+        .layout
+        .process_list_columns
+        .iter()
+        .filter_map(|column| match column {
+            ProcessColumn::Uptime => match (&process.status, process.started_at) {
+                (ProcessStatus::Running, Some(started_at)) => {
+                    Some((format_duration(started_at.elapsed()), false))
+                }
+                _ => None,
+            },
+            ProcessColumn::Restarts => (process.restart_count > 0)
+                .then(|| (format!("{}{}", RESTARTS, process.restart_count), false)),
+            ProcessColumn::Pid => process.pid.map(|pid| (pid.to_string(), false)),
+            ProcessColumn::ExitCode => match process.status {
+                ProcessStatus::Halted => process
+                    .last_exit_code
+                    .map(|code| (format!("exit {}", code), false)),
+                _ => None,
+            },
+            ProcessColumn::Cpu => process.usage.map(|usage| {
+                (
+                    format!("{:.0}%", usage.cpu_percent),
+                    exceeds_cpu_threshold(resources, &usage),
+                )
+            }),
+            ProcessColumn::Memory => process.usage.map(|usage| {
+                (
+                    format_bytes(usage.memory_bytes),
+                    exceeds_memory_threshold(resources, &usage),
+                )
+            }),
+        })
+        .map(|(text, warning)| {
+            let fg = if warning {
+                color_from_config_string(&state.config.style.alert_warn_color)
+                    .unwrap_or(Box::new(color::Yellow))
+            } else {
+                color_from_config_string(&state.config.style.process_column_color)
+                    .unwrap_or(Box::new(color::LightBlack))
+            };
+            ColoredSegment::new_basic(fg, format!(" {}", text))
+        })
+        .collect()
+}
+
+/*
+ * CPU and memory usage of a running process, e.g. for the description panel
+ */
+pub fn get_usage_summary(process: &Process) -> Option<String> {
+    let usage = process.usage?;
+    Some(format!(
+        "cpu {:.1}%  mem {}",
+        usage.cpu_percent,
+        format_bytes(usage.memory_bytes)
+    ))
 }
 
 pub fn color_from_config_string(s: &str) -> Result<Box<dyn Color>, Box<dyn Error>> {
@@ -0,0 +1,219 @@
+/*
+    CPU and memory usage of process trees, sampled from /proc
+*/
+use std::collections::HashMap;
+use std::fs;
+use std::time::Instant;
+
+use crate::config::ResourceConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResourceUsage {
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+}
+
+#[derive(Debug, PartialEq)]
+struct ProcStat {
+    ppid: i32,
+    // user + system time, in clock ticks
+    cpu_ticks: u64,
+    rss_pages: u64,
+}
+
+/*
+ * Parses the contents of /proc/<pid>/stat. The command name (field 2) is
+ * in parentheses and may itself contain spaces or parentheses.
+ */
+fn parse_stat(contents: &str) -> Option<ProcStat> {
+    let fields: Vec<&str> = contents
+        .get(contents.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+    // fields[0] is field 3 (state) of proc(5)
+    let field = |n: usize| fields.get(n - 3);
+    Some(ProcStat {
+        ppid: field(4)?.parse().ok()?,
+        cpu_ticks: field(14)?.parse::<u64>().ok()? + field(15)?.parse::<u64>().ok()?,
+        rss_pages: field(24)?.parse().ok()?,
+    })
+}
+
+fn read_proc_stats() -> HashMap<i32, ProcStat> {
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(e) => {
+            trace!("Unable to read /proc: {}", e);
+            return HashMap::new();
+        }
+    };
+    entries
+        .filter_map(|entry| {
+            let pid: i32 = entry.ok()?.file_name().to_str()?.parse().ok()?;
+            let contents = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            Some((pid, parse_stat(&contents)?))
+        })
+        .collect()
+}
+
+/*
+ * Sums cpu ticks and rss pages of root_pid and all of its descendants
+ */
+fn tree_totals(stats: &HashMap<i32, ProcStat>, root_pid: i32) -> Option<(u64, u64)> {
+    let root = stats.get(&root_pid)?;
+    let mut totals = (root.cpu_ticks, root.rss_pages);
+    let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (pid, stat) in stats.iter() {
+        children.entry(stat.ppid).or_default().push(*pid);
+    }
+    let mut pending = children.get(&root_pid).cloned().unwrap_or_default();
+    while let Some(pid) = pending.pop() {
+        if let Some(stat) = stats.get(&pid) {
+            totals.0 += stat.cpu_ticks;
+            totals.1 += stat.rss_pages;
+        }
+        pending.extend(children.get(&pid).into_iter().flatten());
+    }
+    Some(totals)
+}
+
+pub struct ResourceSampler {
+    clock_ticks_per_sec: f32,
+    page_size: u64,
+    // cpu ticks of each sampled tree (by root pid) when last sampled
+    previous: HashMap<i32, (u64, Instant)>,
+}
+
+impl ResourceSampler {
+    pub fn new() -> Self {
+        let clock_ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        ResourceSampler {
+            clock_ticks_per_sec: if clock_ticks_per_sec > 0 {
+                clock_ticks_per_sec as f32
+            } else {
+                100.0
+            },
+            page_size: if page_size > 0 {
+                page_size as u64
+            } else {
+                4096
+            },
+            previous: HashMap::new(),
+        }
+    }
+
+    /*
+     * Usage of the process tree rooted at each pid. CPU usage is averaged over
+     * the time since the previous sample, so is 0 the first time a pid is sampled.
+     */
+    pub fn sample(&mut self, root_pids: &[i32]) -> HashMap<i32, ResourceUsage> {
+        let stats = read_proc_stats();
+        let now = Instant::now();
+        let mut usages = HashMap::new();
+        let mut previous = HashMap::new();
+        for pid in root_pids.iter() {
+            let (cpu_ticks, rss_pages) = match tree_totals(&stats, *pid) {
+                Some(totals) => totals,
+                None => continue,
+            };
+            let cpu_percent = match self.previous.get(pid) {
+                Some((prev_ticks, prev_time)) if cpu_ticks >= *prev_ticks => {
+                    let elapsed = now.duration_since(*prev_time).as_secs_f32();
+                    if elapsed > 0.0 {
+                        (cpu_ticks - prev_ticks) as f32 / self.clock_ticks_per_sec / elapsed * 100.0
+                    } else {
+                        0.0
+                    }
+                }
+                _ => 0.0,
+            };
+            previous.insert(*pid, (cpu_ticks, now));
+            usages.insert(
+                *pid,
+                ResourceUsage {
+                    cpu_percent,
+                    memory_bytes: rss_pages * self.page_size,
+                },
+            );
+        }
+        self.previous = previous;
+        usages
+    }
+}
+
+pub fn exceeds_cpu_threshold(config: &ResourceConfig, usage: &ResourceUsage) -> bool {
+    config
+        .cpu_warning_percent
+        .is_some_and(|threshold| usage.cpu_percent > threshold as f32)
+}
+
+pub fn exceeds_memory_threshold(config: &ResourceConfig, usage: &ResourceUsage) -> bool {
+    config
+        .memory_warning_mb
+        .is_some_and(|threshold| usage.memory_bytes > threshold * 1024 * 1024)
+}
+
+pub fn exceeds_threshold(config: &ResourceConfig, usage: &ResourceUsage) -> bool {
+    exceeds_cpu_threshold(config, usage) || exceeds_memory_threshold(config, usage)
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "K", "M", "G"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 || value >= 10.0 {
+        format!("{:.0}{}", value, UNITS[unit])
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_stat_handles_parentheses_in_command_name() {
+        let contents = "1234 (my (weird) cmd) S 1 1234 1234 0 -1 4194560 100 0 0 0 \
+                        25 17 0 0 20 0 1 0 500 10000000 321 18446744073709551615";
+        assert_eq!(
+            parse_stat(contents),
+            Some(ProcStat {
+                ppid: 1,
+                cpu_ticks: 42,
+                rss_pages: 321,
+            })
+        );
+        assert_eq!(parse_stat("garbage"), None);
+    }
+
+    #[test]
+    fn tree_totals_includes_descendants_only() {
+        let stat = |ppid, cpu_ticks, rss_pages| ProcStat {
+            ppid,
+            cpu_ticks,
+            rss_pages,
+        };
+        let stats = HashMap::from([
+            (10, stat(1, 1, 10)),
+            (11, stat(10, 2, 20)),
+            (12, stat(11, 4, 40)),
+            (20, stat(1, 8, 80)),
+        ]);
+        assert_eq!(tree_totals(&stats, 10), Some((7, 70)));
+        assert_eq!(tree_totals(&stats, 20), Some((8, 80)));
+        assert_eq!(tree_totals(&stats, 30), None);
+    }
+
+    #[test]
+    fn format_bytes_uses_short_units() {
+        assert_eq!(format_bytes(512), "512B");
+        assert_eq!(format_bytes(1536), "1.5K");
+        assert_eq!(format_bytes(150 * 1024 * 1024), "150M");
+    }
+}
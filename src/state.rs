@@ -1,8 +1,24 @@
 use std::collections::HashSet;
+use std::time::{Duration, Instant};
 
-use crate::config::ProcTmuxConfig;
-use crate::gui_state::GUIState;
-use crate::process::{Process, ProcessStatus};
+use crate::config::{AlertLevel, ProcTmuxConfig};
+use crate::constants::STATUS_HISTORY_LENGTH;
+use crate::fuzzy::FuzzyMatch;
+use crate::gui_state::{GUIState, GUIStateMutation};
+use crate::order::{load_order, sort_processes};
+use crate::process::{Process, ProcessStatus, StatusChange};
+use crate::query::Query;
+use crate::resources::ResourceUsage;
+
+/*
+ * A row of the process list, category headers are only
+ * listed when grouping processes by category
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ListEntry {
+    Category(String),
+    Process(usize),
+}
 
 #[derive(Clone, Debug)]
 pub struct State {
@@ -21,10 +37,20 @@ impl State {
             .enumerate()
             .map(|(ix, (k, v))| Process::new(ix + 1, k, v.clone()))
             .collect();
+        for (group, labels) in config.groups.iter() {
+            for label in labels.iter().filter(|l| !config.procs.contains_key(*l)) {
+                error!("Group {} contains unknown process: {}", group, label);
+            }
+        }
         if config.layout.sort_process_list_alpha {
             trace!("Sorting processes alphabetically");
             processes.sort_by(|proc1, proc2| proc1.label.cmp(&proc2.label));
         }
+        let saved_order = match &config.general.order_file {
+            Some(order_file) => load_order(order_file),
+            None => vec![],
+        };
+        sort_processes(&mut processes, &saved_order);
         State {
             current_proc_id: 0,
             processes,
@@ -33,6 +59,13 @@ impl State {
                 messages: vec![],
                 filter_text: None,
                 entering_filter_text: false,
+                marked_proc_ids: HashSet::new(),
+                selected_category: None,
+                collapsed_categories: HashSet::new(),
+                show_details: false,
+                process_pane_size: config.layout.process_pane_size.clone(),
+                split_view_proc_ids: vec![],
+                popped_out_proc_ids: HashSet::new(),
             },
             exiting: false,
         }
@@ -46,45 +79,197 @@ impl State {
         self.get_process(self.current_proc_id)
     }
 
+    pub fn current_entry(&self) -> ListEntry {
+        match &self.gui_state.selected_category {
+            Some(category) => ListEntry::Category(category.clone()),
+            None => ListEntry::Process(self.current_proc_id),
+        }
+    }
+
+    /*
+     * The category a process is listed under when grouping by category
+     */
+    pub fn process_category<'a>(&self, process: &'a Process) -> Option<&'a String> {
+        process.config.categories.as_ref()?.first()
+    }
+
+    /*
+     * The filtered processes listed under a category
+     */
+    pub fn get_category_processes(&self, category: &str) -> Vec<&Process> {
+        self.category_processes(&self.get_filtered_processes(), category)
+    }
+
+    /*
+     * Like get_category_processes, for processes already filtered
+     */
+    pub fn category_processes<'a>(
+        &self,
+        filtered_procs: &[&'a Process],
+        category: &str,
+    ) -> Vec<&'a Process> {
+        filtered_procs
+            .iter()
+            .filter(|p| self.process_category(p).map(|c| c.as_str()) == Some(category))
+            .copied()
+            .collect()
+    }
+
+    pub fn get_list_entries(&self) -> Vec<ListEntry> {
+        self.list_entries(&self.get_filtered_processes())
+    }
+
+    /*
+     * The rows of the process list for the filtered processes. When grouping
+     * by category, uncategorized processes come first, followed by a header
+     * for each category and (unless the category is collapsed) the processes
+     * beneath it
+     */
+    pub fn list_entries(&self, filtered_procs: &[&Process]) -> Vec<ListEntry> {
+        if !self.config.layout.group_by_category {
+            return filtered_procs
+                .iter()
+                .map(|p| ListEntry::Process(p.id))
+                .collect();
+        }
+        let mut entries: Vec<ListEntry> = filtered_procs
+            .iter()
+            .filter(|p| self.process_category(p).is_none())
+            .map(|p| ListEntry::Process(p.id))
+            .collect();
+        let mut categories: Vec<&String> = vec![];
+        for category in filtered_procs
+            .iter()
+            .filter_map(|p| self.process_category(p))
+        {
+            if !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+        for category in categories {
+            entries.push(ListEntry::Category(category.clone()));
+            if !self.gui_state.collapsed_categories.contains(category) {
+                entries.extend(
+                    filtered_procs
+                        .iter()
+                        .filter(|p| self.process_category(p) == Some(category))
+                        .map(|p| ListEntry::Process(p.id)),
+                );
+            }
+        }
+        entries
+    }
+
     pub fn get_process_by_pid(&self, pid: i32) -> Option<&Process> {
         self.processes.iter().find(|p| p.pid == Some(pid))
     }
 
-    pub fn get_filtered_processes(&self) -> Vec<&Process> {
-        fn filter_by_category(filter_text: &str, proc: &Process) -> bool {
-            proc.config
-                .categories
-                .as_ref()
-                .unwrap_or(&vec![])
-                .contains(&filter_text.to_lowercase())
+    pub fn get_process_by_pane_id(&self, pane_id: &str) -> Option<&Process> {
+        self.processes
+            .iter()
+            .find(|p| p.pane_id.as_deref() == Some(pane_id))
+    }
+
+    pub fn get_group_processes(&self, group: &str) -> Vec<&Process> {
+        match self.config.groups.get(group) {
+            Some(labels) => self
+                .processes
+                .iter()
+                .filter(|p| labels.contains(&p.label))
+                .collect(),
+            None => vec![],
         }
-        fn filter_by_name_or_meta_tags(filter_text: &str, proc: &Process) -> bool {
-            let metas: HashSet<_> = HashSet::from_iter(
-                proc.config
-                    .meta_tags
-                    .as_ref()
-                    .unwrap_or(&vec![])
-                    .iter()
-                    .map(|s| s.to_lowercase()),
-            );
-            proc.label
-                .to_lowercase()
-                .contains(&filter_text.to_lowercase())
-                || metas.contains(&filter_text.to_lowercase())
+    }
+
+    /*
+     * The group the filter query filters by, if any
+     */
+    pub fn filtered_group(&self) -> Option<String> {
+        let filter_text = self.gui_state.filter_text.as_ref()?;
+        Query::parse(filter_text, &self.config.layout)
+            .group()
+            .filter(|group| self.config.groups.contains_key(*group))
+            .map(|group| group.to_string())
+    }
+
+    /*
+     * When a profile is selected, it determines which processes
+     * are autostarted instead of each process' autostart setting
+     */
+    pub fn is_autostart(&self, process: &Process) -> bool {
+        match &self.config.general.profile {
+            Some(profile) => self
+                .config
+                .groups
+                .get(profile)
+                .is_some_and(|labels| labels.contains(&process.label)),
+            None => process.config.autostart,
+        }
+    }
+
+    /*
+     * The processes acted on by the start/stop/restart actions:
+     * every marked process, or if none are marked the current
+     * process (or every process of the selected category)
+     */
+    pub fn get_target_processes(&self) -> Vec<&Process> {
+        if self.gui_state.marked_proc_ids.is_empty() {
+            return match self.current_entry() {
+                ListEntry::Category(category) => self.get_category_processes(&category),
+                ListEntry::Process(_) => self.current_process().into_iter().collect(),
+            };
         }
         self.processes
             .iter()
-            .filter(|proc| {
-                if let Some(filter_text) = &self.gui_state.filter_text {
-                    let prefix = &self.config.layout.category_search_prefix;
-                    if filter_text.starts_with(prefix) {
-                        return filter_by_category(&filter_text[prefix.len()..], proc);
-                    }
-                    return filter_by_name_or_meta_tags(filter_text, proc);
-                }
-                true
+            .filter(|p| self.gui_state.marked_proc_ids.contains(&p.id))
+            .collect()
+    }
+
+    /*
+     * The processes acted on by the start/stop/restart all actions
+     */
+    pub fn get_bulk_action_processes(&self) -> Vec<&Process> {
+        if self.config.general.scope_bulk_actions_to_filter {
+            self.get_filtered_processes()
+        } else {
+            self.processes.iter().collect()
+        }
+    }
+
+    /*
+     * Processes matching the filter query, ranked by score (best first),
+     * with their match. The query is evaluated once for every process,
+     * so callers needing the matches repeatedly (e.g. draw) keep the result.
+     */
+    pub fn get_filter_matches(&self) -> Vec<(&Process, FuzzyMatch)> {
+        let query = self
+            .gui_state
+            .filter_text
+            .as_ref()
+            .map(|filter_text| Query::parse(filter_text, &self.config.layout));
+        let mut matches: Vec<(&Process, FuzzyMatch)> = self
+            .processes
+            .iter()
+            .filter_map(|proc| match &query {
+                Some(query) => query.evaluate(&self.config, proc).map(|m| (proc, m)),
+                None => Some((
+                    proc,
+                    FuzzyMatch {
+                        score: 0,
+                        positions: vec![],
+                    },
+                )),
             })
-            .collect::<Vec<_>>()
+            .collect();
+        matches.sort_by_key(|(_, m)| std::cmp::Reverse(m.score));
+        matches
+    }
+
+    pub fn get_filtered_processes(&self) -> Vec<&Process> {
+        self.get_filter_matches()
+            .into_iter()
+            .map(|(proc, _)| proc)
+            .collect()
     }
 }
 
@@ -110,45 +295,120 @@ impl Mutator<State> for StateMutation {
 }
 
 impl StateMutation {
-    fn select_first_process(mut self) -> Self {
-        let filtered_procs = self.init_state.get_filtered_processes();
-        if let Some(first) = filtered_procs.first() {
-            self.init_state.current_proc_id = first.id;
-        }
+    fn select_entry(mut self, entry: &ListEntry) -> Self {
+        let selected_category = match entry {
+            ListEntry::Category(category) => {
+                self.init_state.current_proc_id = 0;
+                Some(category.clone())
+            }
+            ListEntry::Process(process_id) => {
+                self.init_state.current_proc_id = *process_id;
+                None
+            }
+        };
+        self.init_state.gui_state = GUIStateMutation::on(&self.init_state.gui_state)
+            .set_selected_category(selected_category)
+            .commit();
         self
     }
 
-    fn move_process_selection(mut self, direction: i8) -> Self {
-        let filtered_procs = self.init_state.get_filtered_processes();
-        if filtered_procs.is_empty() {
+    fn move_process_selection(self, direction: i8) -> Self {
+        let entries = self.init_state.get_list_entries();
+        if entries.is_empty() {
             return self;
         }
-        if filtered_procs.len() < 2 {
-            return self.select_first_process();
-        }
-        let available_proc_ids = filtered_procs.iter().map(|p| p.id).collect::<Vec<_>>();
-        let current_idx = available_proc_ids
+        let current_entry = self.init_state.current_entry();
+        let current_idx = match entries.iter().position(|e| *e == current_entry) {
+            Some(idx) => idx,
+            None => return self.select_entry(&entries[0]),
+        };
+        let new_idx = (current_idx as i32 + direction as i32).rem_euclid(entries.len() as i32);
+        self.select_entry(&entries[new_idx as usize])
+    }
+
+    /*
+     * Collapses the category of the current process (selecting its header),
+     * or collapses/expands the selected category header
+     */
+    pub fn toggle_current_category(mut self) -> Self {
+        let category = match self.init_state.current_entry() {
+            ListEntry::Category(category) => category,
+            ListEntry::Process(_) => match self
+                .init_state
+                .current_process()
+                .and_then(|p| self.init_state.process_category(p))
+            {
+                Some(category) => category.clone(),
+                None => return self,
+            },
+        };
+        self.init_state.gui_state = GUIStateMutation::on(&self.init_state.gui_state)
+            .toggle_collapsed_category(&category)
+            .commit();
+        self.select_entry(&ListEntry::Category(category))
+    }
+
+    /*
+     * Swaps the current process with its neighbour in the list.
+     * Processes only move among others in the same category (when grouped)
+     * and with the same pinned and order settings.
+     */
+    pub fn move_current_process(mut self, direction: i8) -> Self {
+        let current = match self.init_state.current_process() {
+            Some(current) => current,
+            None => return self,
+        };
+        let listed: Vec<&Process> = self
+            .init_state
+            .get_list_entries()
             .iter()
-            .position(|&p| p == self.init_state.current_proc_id);
-        if current_idx.is_none() {
-            return self.select_first_process();
-        }
-        let current_idx = current_idx.unwrap();
-        let new_idx = (current_idx as i32 + direction as i32) % filtered_procs.len() as i32;
-        if new_idx < 0 {
-            self.init_state.current_proc_id = available_proc_ids[filtered_procs.len() - 1];
-        } else {
-            self.init_state.current_proc_id = available_proc_ids[new_idx as usize];
+            .filter_map(|entry| match entry {
+                ListEntry::Process(id) => self.init_state.get_process(*id),
+                ListEntry::Category(_) => None,
+            })
+            .collect();
+        let neighbour = listed
+            .iter()
+            .position(|p| p.id == current.id)
+            .and_then(|idx| idx.checked_add_signed(direction as isize))
+            .and_then(|idx| listed.get(idx))
+            .filter(|p| p.config.pinned == current.config.pinned)
+            .filter(|p| p.config.order == current.config.order)
+            .filter(|p| {
+                !self.init_state.config.layout.group_by_category
+                    || self.init_state.process_category(p)
+                        == self.init_state.process_category(current)
+            });
+        let (a, b) = match neighbour {
+            Some(neighbour) => (
+                self.init_state
+                    .processes
+                    .iter()
+                    .position(|p| p.id == current.id),
+                self.init_state
+                    .processes
+                    .iter()
+                    .position(|p| p.id == neighbour.id),
+            ),
+            None => return self,
+        };
+        if let (Some(a), Some(b)) = (a, b) {
+            self.init_state.processes.swap(a, b);
         }
         self
     }
 
+    fn mark_current_process_read(self) -> Self {
+        let current_proc_id = self.init_state.current_proc_id;
+        self.clear_process_alerts(current_proc_id)
+    }
+
     pub fn next_process(self) -> Self {
-        self.move_process_selection(1)
+        self.move_process_selection(1).mark_current_process_read()
     }
 
     pub fn previous_process(self) -> Self {
-        self.move_process_selection(-1)
+        self.move_process_selection(-1).mark_current_process_read()
     }
 
     pub fn set_process_status(mut self, status: ProcessStatus, process_id: usize) -> Self {
@@ -158,8 +418,16 @@ impl StateMutation {
             .iter()
             .map(|p| {
                 let mut p = p.clone();
-                if p.id == process_id {
+                if p.id == process_id && p.status != status {
                     p.status = status.clone();
+                    p.status_history.push(StatusChange {
+                        at: Instant::now(),
+                        status: status.clone(),
+                        exit_code: None,
+                    });
+                    if p.status_history.len() > STATUS_HISTORY_LENGTH {
+                        p.status_history.remove(0);
+                    }
                 }
                 p
             })
@@ -199,6 +467,219 @@ impl StateMutation {
         self
     }
 
+    pub fn set_process_exit_code(mut self, exit_code: Option<i32>, process_id: usize) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.exit_code = exit_code;
+                    if let Some(change) = p
+                        .status_history
+                        .last_mut()
+                        .filter(|c| c.status == ProcessStatus::Halted)
+                    {
+                        change.exit_code = exit_code;
+                    }
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
+    pub fn set_process_started_at(
+        mut self,
+        started_at: Option<Instant>,
+        process_id: usize,
+    ) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.started_at = started_at;
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
+    pub fn set_process_run_duration(
+        mut self,
+        run_duration: Option<Duration>,
+        process_id: usize,
+    ) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.run_duration = run_duration;
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
+    pub fn set_process_last_exit_code(
+        mut self,
+        last_exit_code: Option<i32>,
+        process_id: usize,
+    ) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.last_exit_code = last_exit_code;
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
+    pub fn set_process_exited_at(mut self, exited_at: Option<Instant>, process_id: usize) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.exited_at = exited_at;
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
+    pub fn set_process_restart_count(mut self, restart_count: usize, process_id: usize) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.restart_count = restart_count;
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
+    pub fn set_process_usage(mut self, usage: Option<ResourceUsage>, process_id: usize) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.usage = usage;
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
+    pub fn set_process_restart_pending(mut self, restart_pending: bool, process_id: usize) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.restart_pending = restart_pending;
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
+    pub fn set_process_starting(mut self, starting: bool, process_id: usize) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.starting = starting;
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
+    pub fn set_process_ready(mut self, ready: bool, process_id: usize) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.ready = ready;
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
+    pub fn add_process_alert(mut self, level: AlertLevel, process_id: usize) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.alert = p.alert.max(Some(level));
+                    p.unread_alerts += 1;
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
+    pub fn clear_process_alerts(mut self, process_id: usize) -> Self {
+        self.init_state.processes = self
+            .init_state
+            .processes
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                if p.id == process_id {
+                    p.alert = None;
+                    p.unread_alerts = 0;
+                }
+                p
+            })
+            .collect();
+        self
+    }
+
     pub fn set_gui_state(mut self, gui_state: GUIState) -> Self {
         self.init_state.gui_state = gui_state;
         self
@@ -1,7 +1,192 @@
 use std::collections::HashMap;
 use std::error::Error;
-use std::io::Result as IoResult;
-use std::process::{Child, Command, Output, Stdio};
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex};
+
+use crate::config::SidebarPosition;
+use crate::tmux_daemon::{CommandTimedOut, ControlClient};
+
+/*
+ * The tmux binary and server all tmux commands are run with. The socket is
+ * either a socket name (-L) or, when it contains a '/', a socket path (-S).
+ * Without a socket tmux picks the server, i.e. the one in $TMUX or the default.
+ */
+#[derive(Clone)]
+pub struct TmuxServer {
+    bin: String,
+    socket: Option<String>,
+    // once connected, commands are sent over this control mode client
+    // instead of starting a tmux process for each of them
+    control_client: Arc<Mutex<Option<ControlClient>>>,
+    // the oldest supported version until detected (see with_version)
+    version: TmuxVersion,
+}
+
+impl TmuxServer {
+    pub fn new(bin: &str, socket: Option<&str>) -> Self {
+        TmuxServer {
+            bin: bin.to_string(),
+            socket: socket.map(|s| s.to_string()),
+            control_client: Arc::new(Mutex::new(None)),
+            version: TmuxVersion::MINIMUM,
+        }
+    }
+
+    pub fn with_version(mut self, version: TmuxVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn bin(&self) -> &str {
+        &self.bin
+    }
+
+    pub fn version(&self) -> TmuxVersion {
+        self.version
+    }
+
+    /*
+     * Shared by all clones of this server
+     */
+    pub fn set_control_client(&self, control_client: Option<ControlClient>) {
+        *self.control_client.lock().unwrap() = control_client;
+    }
+
+    fn process(&self) -> Command {
+        let mut command = Command::new(&self.bin);
+        match &self.socket {
+            Some(socket) if socket.contains('/') => command.arg("-S").arg(socket),
+            Some(socket) => command.arg("-L").arg(socket),
+            None => &mut command,
+        };
+        command
+    }
+
+    fn command(&self) -> TmuxCommand<'_> {
+        TmuxCommand {
+            server: self,
+            args: vec![],
+        }
+    }
+}
+
+/*
+ * The version of tmux, deciding which of the features proctmux relies on are available
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TmuxVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl TmuxVersion {
+    // percentage sizes for split-window/join-pane -l
+    pub const MINIMUM: TmuxVersion = TmuxVersion { major: 3, minor: 1 };
+    // refresh-client -B
+    const SUBSCRIPTIONS: TmuxVersion = TmuxVersion { major: 3, minor: 2 };
+    // display-popup
+    const POPUP: TmuxVersion = TmuxVersion { major: 3, minor: 2 };
+    // the pane_dead_signal format
+    const DEAD_SIGNAL: TmuxVersion = TmuxVersion { major: 3, minor: 3 };
+
+    /*
+     * Parses the output of tmux -V, e.g. "tmux 3.3a", "tmux 3.2-rc3" or "tmux next-3.4".
+     * Builds without a version number (e.g. "tmux master") are taken to be the latest.
+     */
+    pub fn parse(output: &str) -> Option<Self> {
+        let version = output.trim().strip_prefix("tmux ")?;
+        let version = version.strip_prefix("next-").unwrap_or(version);
+        if !version.starts_with(|c: char| c.is_ascii_digit()) {
+            return Some(TmuxVersion {
+                major: u32::MAX,
+                minor: u32::MAX,
+            });
+        }
+        let (major, rest) = version.split_once('.')?;
+        let minor: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        Some(TmuxVersion {
+            major: major.parse().ok()?,
+            minor: minor.parse().ok()?,
+        })
+    }
+
+    pub fn is_supported(&self) -> bool {
+        *self >= Self::MINIMUM
+    }
+
+    pub fn supports_subscriptions(&self) -> bool {
+        *self >= Self::SUBSCRIPTIONS
+    }
+
+    pub fn supports_dead_signal(&self) -> bool {
+        *self >= Self::DEAD_SIGNAL
+    }
+
+    pub fn supports_popup(&self) -> bool {
+        *self >= Self::POPUP
+    }
+}
+
+impl std::fmt::Display for TmuxVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.major == u32::MAX {
+            write!(f, "(development build)")
+        } else {
+            write!(f, "{}.{}", self.major, self.minor)
+        }
+    }
+}
+
+/*
+ * A tmux command, built like a std::process::Command
+ */
+pub struct TmuxCommand<'a> {
+    server: &'a TmuxServer,
+    args: Vec<String>,
+}
+
+impl TmuxCommand<'_> {
+    fn arg<S: AsRef<str>>(&mut self, arg: S) -> &mut Self {
+        self.args.push(arg.as_ref().to_string());
+        self
+    }
+
+    fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    /*
+     * Runs the command over the control mode client if one is connected,
+     * falling back to running a tmux process if it could not be sent (e.g.
+     * the client exited) or spans lines. A command tmux did not answer in
+     * time is not run again, it may yet have run.
+     */
+    fn output(&mut self) -> IoResult<Output> {
+        let control_client = self.server.control_client.lock().unwrap().clone();
+        let multi_line = self.args.iter().any(|arg| arg.contains(['\n', '\r']));
+        if let Some(control_client) = control_client.filter(|_| !multi_line) {
+            match control_client.run(&self.args) {
+                Ok(output) => return Ok(output),
+                Err(e) if e.is::<CommandTimedOut>() => {
+                    return Err(IoError::new(ErrorKind::TimedOut, e.to_string()))
+                }
+                Err(e) => error!(
+                    "Error running tmux command over control mode ({}), retrying: {:?}",
+                    e, self.args
+                ),
+            }
+        }
+        self.server.process().args(&self.args).output()
+    }
+}
 
 fn clean_output(s: &str) -> String {
     s.replace("\n", "")
@@ -11,32 +196,47 @@ pub fn read_bytes(output: IoResult<Output>) -> Result<String, Box<dyn Error>> {
     Ok(clean_output(&String::from_utf8(output?.stdout)?))
 }
 
-pub fn list_sessions() -> IoResult<Output> {
-    Command::new("tmux")
+pub fn version(server: &TmuxServer) -> Result<TmuxVersion, Box<dyn Error>> {
+    let output = server
+        .process()
+        .arg("-V")
+        .output()
+        .map_err(|e| format!("Could not run {}: {}", server.bin, e))?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    TmuxVersion::parse(&output)
+        .ok_or_else(|| Box::from(format!("Unknown tmux version: {}", output.trim())))
+}
+
+pub fn list_sessions(server: &TmuxServer) -> IoResult<Output> {
+    server
+        .command()
         .arg("list-sessions")
         .arg("-F")
         .arg("#{session_name}")
         .output()
 }
 
-pub fn current_session() -> IoResult<Output> {
-    Command::new("tmux")
+pub fn current_session(server: &TmuxServer) -> IoResult<Output> {
+    server
+        .command()
         .arg("display-message")
         .arg("-p")
         .arg("#{session_id}")
         .output()
 }
 
-pub fn current_pane() -> IoResult<Output> {
-    Command::new("tmux")
+pub fn current_pane(server: &TmuxServer) -> IoResult<Output> {
+    server
+        .command()
         .arg("display-message")
         .arg("-p")
         .arg("#{pane_id}")
         .output()
 }
 
-pub fn start_detached_session(session_name: &str) -> IoResult<Output> {
-    Command::new("tmux")
+pub fn start_detached_session(server: &TmuxServer, session_name: &str) -> IoResult<Output> {
+    server
+        .command()
         .arg("new-session")
         .arg("-d")
         .arg("-s")
@@ -47,8 +247,44 @@ pub fn start_detached_session(session_name: &str) -> IoResult<Output> {
         .output()
 }
 
-pub fn set_remain_on_exit(pane_id: &str, on: bool) -> IoResult<Output> {
-    Command::new("tmux")
+/*
+ * Starts a new session running command (the program followed by its arguments)
+ */
+pub fn start_session(
+    server: &TmuxServer,
+    working_directory: &str,
+    command: &[String],
+) -> IoResult<Output> {
+    server
+        .command()
+        .arg("new-session")
+        .arg("-d")
+        .arg("-c")
+        .arg(working_directory)
+        .arg("-P")
+        .arg("-F")
+        .arg("#{session_id}")
+        .args(command)
+        .output()
+}
+
+/*
+ * Attaches the terminal to the session, returning once the client detaches
+ * or the session ends. $TMUX is unset, since it may be set for another server.
+ */
+pub fn attach_session(server: &TmuxServer, session_id: &str) -> IoResult<ExitStatus> {
+    server
+        .process()
+        .env_remove("TMUX")
+        .arg("attach-session")
+        .arg("-t")
+        .arg(session_id)
+        .status()
+}
+
+pub fn set_remain_on_exit(server: &TmuxServer, pane_id: &str, on: bool) -> IoResult<Output> {
+    server
+        .command()
         .arg("set-option")
         .arg("-t")
         .arg(pane_id)
@@ -57,8 +293,9 @@ pub fn set_remain_on_exit(pane_id: &str, on: bool) -> IoResult<Output> {
         .output()
 }
 
-pub fn kill_session(session_id: &str) -> IoResult<Output> {
-    Command::new("tmux")
+pub fn kill_session(server: &TmuxServer, session_id: &str) -> IoResult<Output> {
+    server
+        .command()
         .arg("kill-session")
         .arg("-t")
         .arg(session_id)
@@ -66,12 +303,14 @@ pub fn kill_session(session_id: &str) -> IoResult<Output> {
 }
 
 pub fn break_pane(
+    server: &TmuxServer,
     pane_id: &str,
     dest_session: &str,
     dest_window: usize,
     window_label: &str,
 ) -> IoResult<Output> {
-    Command::new("tmux")
+    server
+        .command()
         .arg("break-pane")
         .arg("-d")
         .arg("-s")
@@ -83,13 +322,57 @@ pub fn break_pane(
         .output()
 }
 
-pub fn join_pane(target_pane: &str, dest_pane: &str) -> IoResult<Output> {
-    Command::new("tmux")
+/*
+ * Arguments to split-window/join-pane placing the new pane
+ * beside the sidebar (the target pane)
+ */
+fn split_args(position: SidebarPosition, size: &str) -> Vec<&str> {
+    let mut args = match position {
+        SidebarPosition::Left => vec!["-h"],
+        SidebarPosition::Right => vec!["-h", "-b"],
+        SidebarPosition::Top => vec!["-v"],
+        SidebarPosition::Bottom => vec!["-v", "-b"],
+    };
+    args.extend(["-l", size]);
+    args
+}
+
+pub fn join_pane(
+    server: &TmuxServer,
+    target_pane: &str,
+    dest_pane: &str,
+    position: SidebarPosition,
+    size: &str,
+) -> IoResult<Output> {
+    server
+        .command()
         .arg("join-pane")
         .arg("-d")
-        .arg("-h")
+        .args(split_args(position, size))
+        .arg("-s")
+        .arg(target_pane)
+        .arg("-t")
+        .arg(dest_pane)
+        .output()
+}
+
+/*
+ * Joins target_pane beside (horizontal) or below dest_pane
+ */
+pub fn join_pane_beside(
+    server: &TmuxServer,
+    target_pane: &str,
+    dest_pane: &str,
+    horizontal: bool,
+    size: &str,
+) -> IoResult<Output> {
+    server
+        .command()
+        .arg("join-pane")
+        .arg("-d")
+        .arg(if horizontal { "-h" } else { "-v" })
         .arg("-l")
-        .arg("70%")
+        .arg(size)
         .arg("-s")
         .arg(target_pane)
         .arg("-t")
@@ -97,18 +380,40 @@ pub fn join_pane(target_pane: &str, dest_pane: &str) -> IoResult<Output> {
         .output()
 }
 
-pub fn kill_pane(pane_id: &str) -> IoResult<Output> {
-    Command::new("tmux")
+/*
+ * Moves the pane into a new window of dest_session, making it the current window
+ */
+pub fn break_pane_to_window(
+    server: &TmuxServer,
+    pane_id: &str,
+    dest_session: &str,
+    window_label: &str,
+) -> IoResult<Output> {
+    server
+        .command()
+        .arg("break-pane")
+        .arg("-s")
+        .arg(pane_id)
+        .arg("-t")
+        .arg(format!("{}:", dest_session))
+        .arg("-n")
+        .arg(window_label)
+        .output()
+}
+
+pub fn kill_pane(server: &TmuxServer, pane_id: &str) -> IoResult<Output> {
+    server
+        .command()
         .arg("kill-pane")
         .arg("-t")
         .arg(pane_id)
         .output()
 }
 
-fn add_env_variables<'a>(
-    mut command: &'a mut Command,
+fn add_env_variables<'a, 'b>(
+    mut command: &'a mut TmuxCommand<'b>,
     env: &Option<HashMap<String, Option<String>>>,
-) -> &'a mut Command {
+) -> &'a mut TmuxCommand<'b> {
     if let Some(hm) = env {
         for (k, v) in hm.iter() {
             command =
@@ -121,18 +426,19 @@ fn add_env_variables<'a>(
 }
 
 pub fn create_pane(
+    server: &TmuxServer,
     pane_id: &str,
     command: &str,
     working_directory: &str,
     env: &Option<HashMap<String, Option<String>>>,
+    position: SidebarPosition,
+    size: &str,
 ) -> IoResult<Output> {
-    let mut c = Command::new("tmux");
+    let mut c = server.command();
     add_env_variables(
         c.arg("split-window")
             .arg("-d")
-            .arg("-h")
-            .arg("-l")
-            .arg("70%")
+            .args(split_args(position, size))
             .arg("-t")
             .arg(pane_id)
             .arg("-c")
@@ -147,34 +453,54 @@ pub fn create_pane(
 }
 
 pub fn create_detached_pane(
+    server: &TmuxServer,
     dest_session: &str,
     dest_window: usize,
     window_label: &str,
+    working_directory: &str,
+) -> IoResult<Output> {
+    // the placeholder command keeps the pane alive until the process is respawned into it
+    server
+        .command()
+        .arg("new-window")
+        .arg("-d")
+        .arg("-t")
+        .arg(format!("{}:{}", dest_session, dest_window))
+        .arg("-n")
+        .arg(window_label)
+        .arg("-c")
+        .arg(working_directory)
+        .arg("-P")
+        .arg("-F")
+        .arg("#{pane_id}")
+        .arg("cat")
+        .output()
+}
+
+pub fn respawn_pane(
+    server: &TmuxServer,
+    pane_id: &str,
     command: &str,
     working_directory: &str,
     env: &Option<HashMap<String, Option<String>>>,
 ) -> IoResult<Output> {
-    let mut c = Command::new("tmux");
+    let mut c = server.command();
     add_env_variables(
-        c.arg("new-window")
-            .arg("-d")
+        c.arg("respawn-pane")
+            .arg("-k")
             .arg("-t")
-            .arg(format!("{}:{}", dest_session, dest_window))
-            .arg("-n")
-            .arg(window_label)
+            .arg(pane_id)
             .arg("-c")
-            .arg(working_directory)
-            .arg("-P")
-            .arg("-F")
-            .arg("#{pane_id}"),
+            .arg(working_directory),
         env,
     )
     .arg(command)
     .output()
 }
 
-pub fn get_pane_pid(pane_id: &str) -> IoResult<Output> {
-    Command::new("tmux")
+pub fn get_pane_pid(server: &TmuxServer, pane_id: &str) -> IoResult<Output> {
+    server
+        .command()
         .arg("display-message")
         .arg("-p")
         .arg("-t")
@@ -183,28 +509,142 @@ pub fn get_pane_pid(pane_id: &str) -> IoResult<Output> {
         .output()
 }
 
-pub fn select_pane(pane_id: &str) -> IoResult<Output> {
-    Command::new("tmux")
-        .arg("select-pane")
+/*
+ * direction is one of the resize-pane flags: -L, -R, -U or -D
+ */
+pub fn resize_pane(
+    server: &TmuxServer,
+    pane_id: &str,
+    direction: &str,
+    amount: u16,
+) -> IoResult<Output> {
+    server
+        .command()
+        .arg("resize-pane")
         .arg("-t")
         .arg(pane_id)
+        .arg(direction)
+        .arg(amount.to_string())
         .output()
 }
 
-pub fn pane_variables(pane_id: &str, format: &str) -> IoResult<Output> {
-    Command::new("tmux")
-        .arg("list-panes")
+pub fn pane_format(server: &TmuxServer, pane_id: &str, size_format: &str) -> IoResult<Output> {
+    server
+        .command()
+        .arg("display-message")
+        .arg("-p")
         .arg("-t")
         .arg(pane_id)
-        .arg("-f")
-        .arg(format!("#{{m:{},#{{pane_id}}}}", pane_id))
+        .arg(size_format)
+        .output()
+}
+
+/*
+ * Expands a format for each pane of the session, one line per pane
+ */
+pub fn list_panes(server: &TmuxServer, session: &str, format: &str) -> IoResult<Output> {
+    server
+        .command()
+        .arg("list-panes")
+        .arg("-s")
+        .arg("-t")
+        .arg(session)
         .arg("-F")
         .arg(format)
         .output()
 }
 
-pub fn toggle_zoom(pane_id: &str) -> IoResult<Output> {
-    Command::new("tmux")
+pub fn socket_path(server: &TmuxServer) -> IoResult<Output> {
+    server
+        .command()
+        .arg("display-message")
+        .arg("-p")
+        .arg("#{socket_path}")
+        .output()
+}
+
+/*
+ * Shows a popup over target_pane running command, the returned child
+ * exits once the popup is closed
+ */
+pub fn display_popup(
+    server: &TmuxServer,
+    target_pane: &str,
+    title: &str,
+    command: &str,
+) -> IoResult<Child> {
+    server
+        .process()
+        .arg("display-popup")
+        .arg("-E")
+        .arg("-w")
+        .arg("90%")
+        .arg("-h")
+        .arg("90%")
+        .arg("-t")
+        .arg(target_pane)
+        .arg("-T")
+        .arg(title)
+        .arg(command)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+}
+
+/*
+ * Not sent over control mode, the message would be shown
+ * on the control client instead of the user's client
+ */
+pub fn display_message(server: &TmuxServer, target_pane: &str, message: &str) -> IoResult<Output> {
+    server
+        .process()
+        .arg("display-message")
+        .arg("-t")
+        .arg(target_pane)
+        .arg(message)
+        .output()
+}
+
+pub fn select_pane(server: &TmuxServer, pane_id: &str) -> IoResult<Output> {
+    server
+        .command()
+        .arg("select-pane")
+        .arg("-t")
+        .arg(pane_id)
+        .output()
+}
+
+/*
+ * Marks the pane, which tmux shows with a highlighted border,
+ * without making it the active pane. Marking the marked pane unmarks it.
+ */
+pub fn mark_pane(server: &TmuxServer, pane_id: &str) -> IoResult<Output> {
+    server
+        .command()
+        .arg("select-pane")
+        .arg("-m")
+        .arg("-t")
+        .arg(pane_id)
+        .output()
+}
+
+/*
+ * Unmarks the marked pane, wherever it is
+ */
+pub fn clear_marked_pane(server: &TmuxServer, target_pane: &str) -> IoResult<Output> {
+    server
+        .command()
+        .arg("select-pane")
+        .arg("-M")
+        .arg("-t")
+        .arg(target_pane)
+        .output()
+}
+
+#[allow(dead_code)]
+pub fn toggle_zoom(server: &TmuxServer, pane_id: &str) -> IoResult<Output> {
+    server
+        .command()
         .arg("resize-pane")
         .arg("-Z")
         .arg("-t")
@@ -212,8 +652,9 @@ pub fn toggle_zoom(pane_id: &str) -> IoResult<Output> {
         .output()
 }
 
-pub fn control_mode(session_id: &str) -> IoResult<Child> {
-    Command::new("tmux")
+pub fn control_mode(server: &TmuxServer, session_id: &str) -> IoResult<Child> {
+    server
+        .process()
         .arg("-C")
         .arg("attach-session")
         .arg("-t")
@@ -222,3 +663,29 @@ pub fn control_mode(session_id: &str) -> IoResult<Child> {
         .stdout(Stdio::piped())
         .spawn()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions() {
+        let version = |major, minor| Some(TmuxVersion { major, minor });
+        assert_eq!(TmuxVersion::parse("tmux 3.3a\n"), version(3, 3));
+        assert_eq!(TmuxVersion::parse("tmux 3.2-rc3"), version(3, 2));
+        assert_eq!(TmuxVersion::parse("tmux next-3.4"), version(3, 4));
+        assert_eq!(TmuxVersion::parse("tmux 2.9"), version(2, 9));
+        assert!(TmuxVersion::parse("tmux master")
+            .unwrap()
+            .supports_subscriptions());
+        assert_eq!(TmuxVersion::parse("screen 4.9"), None);
+
+        assert!(!TmuxVersion::parse("tmux 3.0a").unwrap().is_supported());
+        assert!(!TmuxVersion::parse("tmux 3.1c")
+            .unwrap()
+            .supports_subscriptions());
+        assert!(TmuxVersion::parse("tmux 3.2a")
+            .unwrap()
+            .supports_subscriptions());
+    }
+}
@@ -0,0 +1,374 @@
+/*
+    The tmux operations TmuxContext relies on, so they can be run
+    against a real tmux server or simulated (see fake_tmux)
+*/
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Result as IoResult;
+use std::process::{Child, Output};
+
+use crate::config::SidebarPosition;
+use crate::tmux::{self, TmuxServer, TmuxVersion};
+use crate::tmux_daemon::{parse_listed_pane_dead, PANE_DEAD_FORMAT};
+
+pub type Env = Option<HashMap<String, Option<String>>>;
+// the pid of a dead pane, with its exit code if tmux has reaped it
+pub type DeadPane = (i32, Option<i32>);
+
+/*
+ * A pane as listed by tmux, dead once its process has exited
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaneState {
+    pub pane_id: String,
+    pub dead: Option<DeadPane>,
+}
+
+/*
+ * tmux could not find a pane, e.g. because it was killed outside of proctmux
+ */
+#[derive(Debug)]
+pub struct PaneNotFound(pub String);
+
+impl fmt::Display for PaneNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for PaneNotFound {}
+
+pub trait TmuxBackend: Send {
+    /*
+     * Splits target_pane, returning the id of the new pane running command
+     */
+    fn create_pane(
+        &self,
+        target_pane: &str,
+        command: &str,
+        working_directory: &str,
+        env: &Env,
+        position: SidebarPosition,
+        size: &str,
+    ) -> Result<String, Box<dyn Error>>;
+
+    /*
+     * Creates a window in session, returning the id of its (placeholder) pane
+     */
+    fn create_detached_pane(
+        &self,
+        session: &str,
+        window: usize,
+        window_label: &str,
+        working_directory: &str,
+    ) -> Result<String, Box<dyn Error>>;
+
+    fn respawn_pane(
+        &self,
+        pane_id: &str,
+        command: &str,
+        working_directory: &str,
+        env: &Env,
+    ) -> Result<(), Box<dyn Error>>;
+
+    fn set_remain_on_exit(&self, pane_id: &str, on: bool) -> Result<(), Box<dyn Error>>;
+
+    fn break_pane(
+        &self,
+        pane_id: &str,
+        session: &str,
+        window: usize,
+        window_label: &str,
+    ) -> Result<(), Box<dyn Error>>;
+
+    fn break_pane_to_window(
+        &self,
+        pane_id: &str,
+        session: &str,
+        window_label: &str,
+    ) -> Result<(), Box<dyn Error>>;
+
+    fn join_pane(
+        &self,
+        pane_id: &str,
+        dest_pane: &str,
+        position: SidebarPosition,
+        size: &str,
+    ) -> Result<(), Box<dyn Error>>;
+
+    fn join_pane_beside(
+        &self,
+        pane_id: &str,
+        dest_pane: &str,
+        horizontal: bool,
+        size: &str,
+    ) -> Result<(), Box<dyn Error>>;
+
+    fn kill_pane(&self, pane_id: &str) -> Result<(), Box<dyn Error>>;
+
+    fn kill_session(&self, session: &str) -> Result<(), Box<dyn Error>>;
+
+    fn select_pane(&self, pane_id: &str) -> Result<(), Box<dyn Error>>;
+
+    /*
+     * Marks pane_id (unmarking any other pane) without focusing it,
+     * or unmarks the marked pane if pane_id is None.
+     * target_pane is any existing pane.
+     */
+    fn mark_pane(&self, target_pane: &str, pane_id: Option<&str>) -> Result<(), Box<dyn Error>>;
+
+    fn pane_pid(&self, pane_id: &str) -> Result<i32, Box<dyn Error>>;
+
+    /*
+     * The panes in (any window of) session
+     */
+    fn panes(&self, session: &str) -> Result<Vec<PaneState>, Box<dyn Error>>;
+
+    /*
+     * direction is one of the resize-pane flags: -L, -R, -U or -D
+     */
+    fn resize_pane(
+        &self,
+        pane_id: &str,
+        direction: &str,
+        amount: u16,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /*
+     * Expands a format (e.g. #{pane_width}) for the pane
+     */
+    fn pane_format(&self, pane_id: &str, format: &str) -> Result<String, Box<dyn Error>>;
+
+    fn toggle_zoom(&self, pane_id: &str) -> Result<(), Box<dyn Error>>;
+
+    fn display_message(&self, target_pane: &str, message: &str) -> Result<(), Box<dyn Error>>;
+
+    /*
+     * Shows the window of session in a popup over target_pane,
+     * the returned child exits once the popup is closed
+     */
+    fn popup_window(
+        &self,
+        target_pane: &str,
+        session: &str,
+        window: usize,
+        title: &str,
+    ) -> Result<Child, Box<dyn Error>>;
+
+    fn version(&self) -> TmuxVersion;
+}
+
+impl TmuxBackend for TmuxServer {
+    fn create_pane(
+        &self,
+        target_pane: &str,
+        command: &str,
+        working_directory: &str,
+        env: &Env,
+        position: SidebarPosition,
+        size: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        tmux::read_bytes(tmux::create_pane(
+            self,
+            target_pane,
+            command,
+            working_directory,
+            env,
+            position,
+            size,
+        ))
+    }
+
+    fn create_detached_pane(
+        &self,
+        session: &str,
+        window: usize,
+        window_label: &str,
+        working_directory: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        tmux::read_bytes(tmux::create_detached_pane(
+            self,
+            session,
+            window,
+            window_label,
+            working_directory,
+        ))
+    }
+
+    fn respawn_pane(
+        &self,
+        pane_id: &str,
+        command: &str,
+        working_directory: &str,
+        env: &Env,
+    ) -> Result<(), Box<dyn Error>> {
+        tmux::respawn_pane(self, pane_id, command, working_directory, env)?;
+        Ok(())
+    }
+
+    fn set_remain_on_exit(&self, pane_id: &str, on: bool) -> Result<(), Box<dyn Error>> {
+        tmux::set_remain_on_exit(self, pane_id, on)?;
+        Ok(())
+    }
+
+    fn break_pane(
+        &self,
+        pane_id: &str,
+        session: &str,
+        window: usize,
+        window_label: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        check_pane_found(tmux::break_pane(
+            self,
+            pane_id,
+            session,
+            window,
+            window_label,
+        ))
+    }
+
+    fn break_pane_to_window(
+        &self,
+        pane_id: &str,
+        session: &str,
+        window_label: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        check_pane_found(tmux::break_pane_to_window(
+            self,
+            pane_id,
+            session,
+            window_label,
+        ))
+    }
+
+    fn join_pane(
+        &self,
+        pane_id: &str,
+        dest_pane: &str,
+        position: SidebarPosition,
+        size: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        check_pane_found(tmux::join_pane(self, pane_id, dest_pane, position, size))
+    }
+
+    fn join_pane_beside(
+        &self,
+        pane_id: &str,
+        dest_pane: &str,
+        horizontal: bool,
+        size: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        check_pane_found(tmux::join_pane_beside(
+            self, pane_id, dest_pane, horizontal, size,
+        ))
+    }
+
+    fn kill_pane(&self, pane_id: &str) -> Result<(), Box<dyn Error>> {
+        check_pane_found(tmux::kill_pane(self, pane_id))
+    }
+
+    fn kill_session(&self, session: &str) -> Result<(), Box<dyn Error>> {
+        tmux::kill_session(self, session)?;
+        Ok(())
+    }
+
+    fn select_pane(&self, pane_id: &str) -> Result<(), Box<dyn Error>> {
+        tmux::select_pane(self, pane_id)?;
+        Ok(())
+    }
+
+    fn mark_pane(&self, target_pane: &str, pane_id: Option<&str>) -> Result<(), Box<dyn Error>> {
+        tmux::clear_marked_pane(self, target_pane)?;
+        if let Some(pane_id) = pane_id {
+            check_pane_found(tmux::mark_pane(self, pane_id))?;
+        }
+        Ok(())
+    }
+
+    fn pane_pid(&self, pane_id: &str) -> Result<i32, Box<dyn Error>> {
+        Ok(tmux::read_bytes(tmux::get_pane_pid(self, pane_id))?.parse()?)
+    }
+
+    fn panes(&self, session: &str) -> Result<Vec<PaneState>, Box<dyn Error>> {
+        let format = format!("#{{pane_id}} {}", PANE_DEAD_FORMAT);
+        let output = tmux::list_panes(self, session, &format)?;
+        if !output.status.success() {
+            return Err(Box::from(String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(String::from_utf8(output.stdout)?
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(pane_id, value)| PaneState {
+                pane_id: pane_id.to_string(),
+                dead: parse_listed_pane_dead(value),
+            })
+            .collect())
+    }
+
+    fn resize_pane(
+        &self,
+        pane_id: &str,
+        direction: &str,
+        amount: u16,
+    ) -> Result<(), Box<dyn Error>> {
+        tmux::resize_pane(self, pane_id, direction, amount)?;
+        Ok(())
+    }
+
+    fn pane_format(&self, pane_id: &str, format: &str) -> Result<String, Box<dyn Error>> {
+        tmux::read_bytes(tmux::pane_format(self, pane_id, format))
+    }
+
+    fn toggle_zoom(&self, pane_id: &str) -> Result<(), Box<dyn Error>> {
+        tmux::toggle_zoom(self, pane_id)?;
+        Ok(())
+    }
+
+    fn display_message(&self, target_pane: &str, message: &str) -> Result<(), Box<dyn Error>> {
+        tmux::display_message(self, target_pane, message)?;
+        Ok(())
+    }
+
+    /*
+     * Attaches a nested client to the window, detaching (or exiting) closes the popup
+     */
+    fn popup_window(
+        &self,
+        target_pane: &str,
+        session: &str,
+        window: usize,
+        title: &str,
+    ) -> Result<Child, Box<dyn Error>> {
+        let socket_path = tmux::read_bytes(tmux::socket_path(self))?;
+        let command = format!(
+            "TMUX= {} -S {} attach-session -t {}",
+            shell_quote(self.bin()),
+            shell_quote(&socket_path),
+            shell_quote(&format!("{}:{}", session, window))
+        );
+        trace!("Showing popup: {}", command);
+        Ok(tmux::display_popup(self, target_pane, title, &command)?)
+    }
+
+    fn version(&self) -> TmuxVersion {
+        TmuxServer::version(self)
+    }
+}
+
+/*
+ * Fails with PaneNotFound if tmux could not find a pane, other
+ * failures of these commands are ignored (as they always were)
+ */
+fn check_pane_found(output: IoResult<Output>) -> Result<(), Box<dyn Error>> {
+    let output = output?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() && stderr.contains("can't find pane") {
+        return Err(Box::new(PaneNotFound(stderr.trim().to_string())));
+    }
+    Ok(())
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
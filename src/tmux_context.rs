@@ -1,12 +1,64 @@
 use std::collections::HashSet;
+use std::env;
 use std::error::Error;
-use std::io::Result as IoResult;
-use std::process::Output;
+use std::fs;
+use std::process::Child;
 
+use crate::config::{SidebarPosition, SplitViewLayout};
 use crate::process::Process;
-use crate::tmux;
+use crate::tmux::{self, TmuxServer, TmuxVersion};
+use crate::tmux_backend::{PaneState, TmuxBackend};
+
+/*
+ * Whether proctmux runs in a pane of the configured server, i.e. the socket
+ * in $TMUX is that of the server. False if the server is not running.
+ */
+pub fn is_inside_server(server: &TmuxServer) -> bool {
+    let client_socket = match env::var("TMUX") {
+        Ok(value) => value.split(',').next().unwrap_or_default().to_string(),
+        Err(_) => return false,
+    };
+    let server_socket = match tmux::read_bytes(tmux::socket_path(server)) {
+        Ok(path) => path,
+        Err(_) => return false,
+    };
+    match (
+        fs::canonicalize(&client_socket),
+        fs::canonicalize(&server_socket),
+    ) {
+        (Ok(client_socket), Ok(server_socket)) => client_socket == server_socket,
+        _ => client_socket == server_socket,
+    }
+}
+
+/*
+ * Runs proctmux (with the same arguments) in the first pane of a new session
+ * and attaches to it, for when proctmux is not started inside tmux
+ */
+pub fn run_in_new_session(server: &TmuxServer) -> Result<(), Box<dyn Error>> {
+    let mut command = vec![env::current_exe()?.to_string_lossy().to_string()];
+    command.extend(env::args().skip(1));
+    let cwd = env::current_dir()?.to_string_lossy().to_string();
+    let output = tmux::start_session(server, &cwd, &command)?;
+    if !output.status.success() {
+        return Err(Box::from(format!(
+            "Could not start a tmux session: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let session_id = tmux::read_bytes(Ok(output))?;
+    let status = tmux::attach_session(server, &session_id)?;
+    if !status.success() {
+        return Err(Box::from(format!(
+            "Could not attach to tmux session {}",
+            session_id
+        )));
+    }
+    Ok(())
+}
 
 pub struct TmuxContext {
+    backend: Box<dyn TmuxBackend>,
     pub pane_id: String,
     pub session_id: String,
     pub detached_session_id: String,
@@ -14,39 +66,34 @@ pub struct TmuxContext {
 
 impl TmuxContext {
     pub fn new(
+        server: TmuxServer,
         detached_session: &str,
         kill_existing_session: bool,
     ) -> Result<Self, Box<dyn Error>> {
-        let pane_id = match tmux::read_bytes(tmux::current_pane()) {
-            Ok(val) => val,
-            Err(e) => panic!("Error: Could not retrieve tmux pane id: {}", e),
-        };
-        let session_id = match tmux::read_bytes(tmux::current_session()) {
-            Ok(val) => val,
-            Err(e) => panic!("Error: Could not retrieve tmux session id: {}", e),
-        };
+        let pane_id = tmux::read_bytes(tmux::current_pane(&server))
+            .map_err(|e| format!("Could not retrieve tmux pane id: {}", e))?;
+        let session_id = tmux::read_bytes(tmux::current_session(&server))
+            .map_err(|e| format!("Could not retrieve tmux session id: {}", e))?;
+
+        let existing_session_names: HashSet<String> =
+            tmux::read_bytes(tmux::list_sessions(&server))?
+                .split("\n")
+                .map(|s| s.to_string())
+                .collect();
 
-        let existing_session_names: HashSet<String> = tmux::read_bytes(tmux::list_sessions())?
-            .split("\n")
-            .map(|s| s.to_string())
-            .collect();
-
-        let detached_session_id = match {
-            if existing_session_names.contains(detached_session) {
-                if kill_existing_session {
-                    info!("Killing existing session: {}", detached_session);
-                    tmux::kill_session(detached_session)?;
-                    tmux::read_bytes(tmux::start_detached_session(detached_session))
-                } else {
-                    panic!("Session '{}' already exists", detached_session);
-                }
-            } else {
-                tmux::read_bytes(tmux::start_detached_session(detached_session))
+        if existing_session_names.contains(detached_session) {
+            if !kill_existing_session {
+                return Err(Box::from(format!(
+                    "Session '{}' already exists",
+                    detached_session
+                )));
             }
-        } {
-            Ok(val) => val,
-            Err(e) => panic!("Error: Could not retrieve tmux detached session id: {}", e),
-        };
+            info!("Killing existing session: {}", detached_session);
+            tmux::kill_session(&server, detached_session)?;
+        }
+        let detached_session_id =
+            tmux::read_bytes(tmux::start_detached_session(&server, detached_session))
+                .map_err(|e| format!("Could not retrieve tmux detached session id: {}", e))?;
 
         info!(
             "creating tmux context: pane_id: {}, session: {}, detached_session: {}",
@@ -54,20 +101,40 @@ impl TmuxContext {
         );
 
         Ok(TmuxContext {
+            backend: Box::new(server),
             pane_id,
             session_id,
             detached_session_id,
         })
     }
 
-    pub fn prepare(&self) -> IoResult<Output> {
-        tmux::set_remain_on_exit(&self.pane_id, true)
+    /*
+     * A context running its tmux operations against backend,
+     * for a sidebar pane and sessions which already exist
+     */
+    #[cfg(test)]
+    pub fn with_backend(
+        backend: Box<dyn TmuxBackend>,
+        pane_id: &str,
+        session_id: &str,
+        detached_session_id: &str,
+    ) -> Self {
+        TmuxContext {
+            backend,
+            pane_id: pane_id.to_string(),
+            session_id: session_id.to_string(),
+            detached_session_id: detached_session_id.to_string(),
+        }
+    }
+
+    pub fn prepare(&self) -> Result<(), Box<dyn Error>> {
+        self.backend.set_remain_on_exit(&self.pane_id, true)
     }
 
-    pub fn cleanup(&self) -> IoResult<Output> {
-        let output = tmux::kill_session(&self.detached_session_id);
-        tmux::set_remain_on_exit(&self.pane_id, false)?;
-        output
+    pub fn cleanup(&self) -> Result<(), Box<dyn Error>> {
+        let result = self.backend.kill_session(&self.detached_session_id);
+        self.backend.set_remain_on_exit(&self.pane_id, false)?;
+        result
     }
 
     pub fn break_pane(
@@ -75,70 +142,221 @@ impl TmuxContext {
         pane_id: &str,
         dest_window: usize,
         window_label: &str,
-    ) -> IoResult<Output> {
+    ) -> Result<(), Box<dyn Error>> {
         trace!(
             "breaking pane: pane_id: {}, dest_window: {}, window_label: {}",
             pane_id,
             dest_window,
             window_label
         );
-        let output = tmux::break_pane(
+        let result = self.backend.break_pane(
             pane_id,
             &self.detached_session_id,
             dest_window,
             window_label,
         );
-        tmux::set_remain_on_exit(pane_id, true)?;
-        output
+        self.backend.set_remain_on_exit(pane_id, true)?;
+        result
     }
 
-    pub fn join_pane(&self, pane_id: &str) -> IoResult<Output> {
+    /*
+     * Moves the pane into its own window in the session proctmux runs in
+     */
+    pub fn pop_out_pane(&self, pane_id: &str, window_label: &str) -> Result<(), Box<dyn Error>> {
+        trace!(
+            "Popping out pane_id: {} to session: {}",
+            pane_id,
+            self.session_id
+        );
+        self.backend
+            .break_pane_to_window(pane_id, &self.session_id, window_label)
+    }
+
+    /*
+     * Shows the window of a process in the detached session in a popup by
+     * attaching a nested client to it. Detaching (or exiting) closes the popup.
+     */
+    pub fn popup_window(&self, window: usize, title: &str) -> Result<Child, Box<dyn Error>> {
+        self.backend
+            .popup_window(&self.pane_id, &self.detached_session_id, window, title)
+    }
+
+    pub fn version(&self) -> TmuxVersion {
+        self.backend.version()
+    }
+
+    /*
+     * Highlights the pane (or none) while the sidebar keeps focus
+     */
+    pub fn mark_pane(&self, pane_id: Option<&str>) -> Result<(), Box<dyn Error>> {
+        trace!("Marking pane_id: {:?}", pane_id);
+        self.backend.mark_pane(&self.pane_id, pane_id)
+    }
+
+    pub fn join_pane(
+        &self,
+        pane_id: &str,
+        position: SidebarPosition,
+        size: &str,
+    ) -> Result<(), Box<dyn Error>> {
         trace!("Joining pane_id: {} to pane_id: {}", pane_id, self.pane_id);
-        tmux::join_pane(pane_id, &self.pane_id)
+        self.backend
+            .join_pane(pane_id, &self.pane_id, position, size)
+    }
+
+    /*
+     * Joins the panes beside the sidebar in a grid of evenly sized rows
+     * and columns. Each split gives the new pane its share of the space
+     * remaining, e.g. the second of three rows takes 2/3 of the first row.
+     */
+    pub fn join_split_view(
+        &self,
+        pane_ids: &[&str],
+        layout: SplitViewLayout,
+        position: SidebarPosition,
+        size: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if pane_ids.is_empty() {
+            return Ok(());
+        }
+        let columns = match layout {
+            SplitViewLayout::EvenVertical => 1,
+            SplitViewLayout::EvenHorizontal => pane_ids.len(),
+            SplitViewLayout::Tiled => (pane_ids.len() as f64).sqrt().ceil() as usize,
+        };
+        let rows: Vec<&[&str]> = pane_ids.chunks(columns).collect();
+        fn share(remaining: usize) -> String {
+            format!("{}%", 100 * (remaining - 1) / remaining)
+        }
+
+        trace!("Joining split view panes: {:?}", rows);
+        self.join_pane(rows[0][0], position, size)?;
+        for (idx, row) in rows.iter().enumerate().skip(1) {
+            self.backend.join_pane_beside(
+                row[0],
+                rows[idx - 1][0],
+                false,
+                &share(rows.len() - idx + 1),
+            )?;
+        }
+        for row in rows.iter() {
+            for (idx, pane_id) in row.iter().enumerate().skip(1) {
+                self.backend.join_pane_beside(
+                    pane_id,
+                    row[idx - 1],
+                    true,
+                    &share(row.len() - idx + 1),
+                )?;
+            }
+        }
+        Ok(())
     }
 
-    pub fn create_pane(&self, process: &Process) -> Result<String, Box<dyn Error>> {
+    pub fn create_pane(
+        &self,
+        process: &Process,
+        position: SidebarPosition,
+        size: &str,
+    ) -> Result<String, Box<dyn Error>> {
         trace!("Creating pane: {}", process.label);
-        tmux::read_bytes(tmux::create_pane(
+        self.backend.create_pane(
             &self.pane_id,
             &process.command(),
             &process.config.cwd,
             &process.config.env,
-        ))
+            position,
+            size,
+        )
+    }
+
+    /*
+     * Grows/shrinks the process pane by moving its border with the sidebar.
+     * Returns the new size of the process pane (in cells), in the dimension
+     * the pane was split.
+     */
+    pub fn resize_pane(
+        &self,
+        pane_id: &str,
+        position: SidebarPosition,
+        grow: bool,
+        amount: u16,
+    ) -> Result<String, Box<dyn Error>> {
+        let (direction, size_format) = match (position, grow) {
+            (SidebarPosition::Left, true) | (SidebarPosition::Right, false) => {
+                ("-L", "#{pane_width}")
+            }
+            (SidebarPosition::Left, false) | (SidebarPosition::Right, true) => {
+                ("-R", "#{pane_width}")
+            }
+            (SidebarPosition::Top, true) | (SidebarPosition::Bottom, false) => {
+                ("-U", "#{pane_height}")
+            }
+            (SidebarPosition::Top, false) | (SidebarPosition::Bottom, true) => {
+                ("-D", "#{pane_height}")
+            }
+        };
+        trace!("Resizing pane_id: {} {} {}", pane_id, direction, amount);
+        self.backend.resize_pane(pane_id, direction, amount)?;
+        self.backend.pane_format(pane_id, size_format)
     }
 
     pub fn get_pane_pid(&self, pane_id: &str) -> Result<i32, Box<dyn Error>> {
-        Ok(tmux::read_bytes(tmux::get_pane_pid(pane_id))?.parse()?)
+        self.backend.pane_pid(pane_id)
+    }
+
+    /*
+     * The panes beside the sidebar, popped out, or in the detached session
+     */
+    pub fn panes(&self) -> Result<Vec<PaneState>, Box<dyn Error>> {
+        let mut panes = self.backend.panes(&self.session_id)?;
+        panes.extend(self.backend.panes(&self.detached_session_id)?);
+        Ok(panes)
     }
 
+    /*
+     * remain-on-exit must be set before the process is started, otherwise the
+     * pane of a process which exits immediately is gone before it can be reviewed
+     */
     pub fn create_detached_pane(&self, process: &Process) -> Result<String, Box<dyn Error>> {
         trace!("Creating detached pane: {}", process.label);
-        let output = tmux::read_bytes(tmux::create_detached_pane(
+        let pane_id = self.backend.create_detached_pane(
             &self.detached_session_id,
             process.id,
             &process.label,
+            &process.config.cwd,
+        )?;
+        self.backend.set_remain_on_exit(&pane_id, true)?;
+        self.backend.respawn_pane(
+            &pane_id,
             &process.command(),
             &process.config.cwd,
             &process.config.env,
-        ));
-        match &output {
-            Ok(pane_id) => {
-                let _ = tmux::set_remain_on_exit(pane_id, true);
-            }
-            Err(_) => {}
-        }
-        output
+        )?;
+        Ok(pane_id)
     }
 
+    pub fn kill_pane(&self, pane_id: &str) -> Result<(), Box<dyn Error>> {
+        self.backend.kill_pane(pane_id)
+    }
+
+    pub fn select_pane(&self, pane_id: &str) -> Result<(), Box<dyn Error>> {
+        self.backend.select_pane(pane_id)
+    }
+
+    pub fn display_message(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.backend.display_message(&self.pane_id, message)
+    }
+
+    #[allow(dead_code)]
     pub fn is_zoomed_in(&self) -> bool {
-        let output = tmux::read_bytes(tmux::pane_variables(
-            &self.pane_id,
-            "#{window_zoomed_flag} #{pane_active}",
-        ))
-        .unwrap_or("".to_string());
+        let output = self
+            .backend
+            .pane_format(&self.pane_id, "#{window_zoomed_flag} #{pane_active}")
+            .unwrap_or("".to_string());
         output == "1 1"
     }
 
+    #[allow(dead_code)]
     pub fn zoom_in(&self) -> Result<(), Box<dyn Error>> {
         if !self.is_zoomed_in() {
             self.toggle_zoom()?;
@@ -146,6 +364,7 @@ impl TmuxContext {
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub fn zoom_out(&self) -> Result<(), Box<dyn Error>> {
         if self.is_zoomed_in() {
             self.toggle_zoom()?;
@@ -153,7 +372,8 @@ impl TmuxContext {
         Ok(())
     }
 
-    pub fn toggle_zoom(&self) -> IoResult<Output> {
-        tmux::toggle_zoom(&self.pane_id)
+    #[allow(dead_code)]
+    pub fn toggle_zoom(&self) -> Result<(), Box<dyn Error>> {
+        self.backend.toggle_zoom(&self.pane_id)
     }
 }
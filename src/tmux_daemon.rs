@@ -1,52 +1,274 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
+use std::fmt;
 use std::io::{BufRead, BufReader, Write};
-use std::process::{Child, ChildStdin, ChildStdout, ExitStatus};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, ChildStdin, ChildStdout, ExitStatus, Output};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::Sender;
-use std::sync::Arc;
-use std::thread::spawn;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::Duration;
 
-use crate::tmux;
+use crate::alert::strip_ansi;
+use crate::tmux::{self, TmuxServer, TmuxVersion};
 
+// partial output lines longer than this are emitted without waiting for a newline
+const MAX_PENDING_OUTPUT: usize = 4096;
+// how long to wait for tmux to answer a command sent over control mode
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+// how often to try subscribing before giving up, and the delay between tries
+const SUBSCRIBE_ATTEMPTS: u32 = 100;
+const SUBSCRIBE_RETRY_DELAY: Duration = Duration::from_millis(10);
+// how often the panes are checked for dead processes without subscriptions
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// how long to wait before starting an exited control mode client again
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+// pane_dead_signal is empty before tmux 3.3, which does not know it
+pub const PANE_DEAD_FORMAT: &str =
+    "#{pane_dead} #{pane_pid} #{pane_dead_status}/#{pane_dead_signal}";
+
+// the output lines of a command, or its error message
+type CommandResult = Result<Vec<String>, String>;
+
+/*
+ * tmux did not answer a command in time. It may still run it,
+ * so the command must not be run again some other way.
+ */
+#[derive(Debug)]
+pub struct CommandTimedOut(pub Vec<String>);
+
+impl fmt::Display for CommandTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tmux did not answer {:?} in time", self.0)
+    }
+}
+
+impl Error for CommandTimedOut {}
+
+#[derive(Debug, PartialEq)]
+pub enum TmuxEvent {
+    PaneDead { pid: i32, exit_code: Option<i32> },
+    PaneOutput { pane_id: String, line: String },
+    // a window was closed, e.g. by killing its only pane
+    WindowClosed,
+}
+
+/*
+ * Runs commands over a control mode connection. tmux answers commands in the
+ * order it receives them, each with a %begin ... %end (or %error) block, so
+ * every command sent queues a sender for the next answer.
+ */
+#[derive(Clone)]
+pub struct ControlClient {
+    stdin: Arc<Mutex<ChildStdin>>,
+    pending: Arc<Mutex<VecDeque<Sender<CommandResult>>>>,
+}
+
+impl ControlClient {
+    /*
+     * Commands are sent one per line, so arguments spanning lines (which
+     * single quotes would keep as they are) are refused instead of splitting
+     * the command in two and answering the wrong senders
+     */
+    fn send(&self, args: &[String]) -> std::io::Result<std::sync::mpsc::Receiver<CommandResult>> {
+        if args.iter().any(|arg| arg.contains(['\n', '\r'])) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "arguments spanning lines can not be sent over control mode",
+            ));
+        }
+        let (sender, receiver) = channel();
+        let line = format!(
+            "{}\n",
+            args.iter()
+                .map(|arg| quote_argument(arg))
+                .collect::<Vec<String>>()
+                .join(" ")
+        );
+        // holding stdin while queueing keeps the queue in the order commands are written
+        let mut stdin = self.stdin.lock().unwrap();
+        self.pending.lock().unwrap().push_back(sender);
+        if let Err(e) = stdin.write_all(line.as_bytes()).and_then(|_| stdin.flush()) {
+            self.pending.lock().unwrap().pop_back();
+            return Err(e);
+        }
+        Ok(receiver)
+    }
+
+    /*
+     * Runs a tmux command (without the leading "tmux"), returning its
+     * output the way running it in a tmux process would
+     */
+    pub fn run(&self, args: &[String]) -> Result<Output, Box<dyn Error>> {
+        trace!("Control mode command: {:?}", args);
+        let result = match self.send(args)?.recv_timeout(COMMAND_TIMEOUT) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => return Err(Box::new(CommandTimedOut(args.to_vec()))),
+            Err(e) => return Err(Box::new(e)),
+        };
+        let (code, stdout, stderr) = match result {
+            Ok(lines) => (0, lines, vec![]),
+            Err(message) => (1, vec![], vec![message]),
+        };
+        let to_bytes = |lines: Vec<String>| {
+            lines
+                .iter()
+                .map(|line| format!("{}\n", line))
+                .collect::<String>()
+                .into_bytes()
+        };
+        Ok(Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: to_bytes(stdout),
+            stderr: to_bytes(stderr),
+        })
+    }
+
+    /*
+     * Sends further commands to a new control mode client. Commands sent
+     * to the previous one are never answered, so their senders are dropped.
+     */
+    fn reconnect(&self, stdin: ChildStdin) {
+        let mut current = self.stdin.lock().unwrap();
+        *current = stdin;
+        self.pending.lock().unwrap().clear();
+    }
+
+    fn answer(&self, result: CommandResult) {
+        match self.pending.lock().unwrap().pop_front() {
+            // the receiver is gone if the command timed out or its answer is not needed
+            Some(sender) => sender.send(result).unwrap_or(()),
+            None => error!("Received an answer to a command that was not sent"),
+        }
+    }
+}
+
+/*
+ * A control mode client attached to a session, reporting the panes dying and
+ * their output. Clones share the same client, which is started again (by a
+ * watchdog thread) if it exits before being killed.
+ */
+#[derive(Clone)]
 pub struct TmuxDaemon {
+    server: TmuxServer,
     session_id: String,
-    process: Child,
-    stdout: Option<ChildStdout>,
-    stdin: ChildStdin,
+    process: Arc<Mutex<Child>>,
+    stdout: Arc<Mutex<Option<ChildStdout>>>,
+    control_client: ControlClient,
     running: Arc<AtomicBool>,
     subscription_name: String,
+    version: TmuxVersion,
 }
 
 impl TmuxDaemon {
-    pub fn new(session_id: &str) -> Result<Self, Box<dyn Error>> {
+    pub fn new(server: &TmuxServer, session_id: &str) -> Result<Self, Box<dyn Error>> {
         info!(
             "Starting tmux control mode (Session {}) process",
             session_id
         );
-        let mut process = tmux::control_mode(session_id)?;
+        let mut process = tmux::control_mode(server, session_id)?;
         let stdin = process.stdin.take().unwrap();
         let stdout = process.stdout.take();
 
         Ok(TmuxDaemon {
+            server: server.clone(),
             session_id: session_id.to_string(),
-            process,
-            stdout,
-            stdin,
+            process: Arc::new(Mutex::new(process)),
+            stdout: Arc::new(Mutex::new(stdout)),
+            control_client: ControlClient {
+                stdin: Arc::new(Mutex::new(stdin)),
+                pending: Arc::new(Mutex::new(VecDeque::new())),
+            },
             running: Arc::new(AtomicBool::new(true)),
             subscription_name: format!("pane_dead_notification_{}", clean(session_id)),
+            version: server.version(),
         })
     }
 
-    fn subscribe_to_pane_dead_notifications(&mut self) -> std::io::Result<()> {
+    pub fn control_client(&self) -> ControlClient {
+        self.control_client.clone()
+    }
+
+    /*
+     * Sent again until tmux accepts it, in case tmux answers refresh-client -B
+     * with "not a control client" while the client is still starting
+     */
+    fn subscribe_to_pane_dead_notifications(&self) -> Result<(), Box<dyn Error>> {
         info!(
             "Starting subscription (Session: {}): {}",
             self.session_id, self.subscription_name
         );
-        let cmd = format!(
-            "refresh-client -B {}:%*:\"#{{pane_dead}} #{{pane_pid}}\"\n",
-            self.subscription_name
+        let args = [
+            "refresh-client".to_string(),
+            "-B".to_string(),
+            format!("{}:%*:{}", self.subscription_name, PANE_DEAD_FORMAT),
+        ];
+        for attempt in 1..=SUBSCRIBE_ATTEMPTS {
+            let output = self.control_client.run(&args)?;
+            if output.status.success() {
+                return Ok(());
+            }
+            warn!(
+                "Subscription (Session: {}) not accepted (attempt {}): {}",
+                self.session_id,
+                attempt,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            sleep(SUBSCRIBE_RETRY_DELAY);
+        }
+        Err(Box::from(format!(
+            "tmux did not accept the subscription for session {}",
+            self.session_id
+        )))
+    }
+
+    /*
+     * Without subscriptions (before tmux 3.2) the panes of the session are
+     * listed periodically, reporting the processes which died since
+     */
+    fn poll_pane_dead(&self, sender: Sender<TmuxEvent>) {
+        info!(
+            "Polling for dead panes (Session: {}) every {:?}",
+            self.session_id, POLL_INTERVAL
         );
-        self.stdin.write_all(cmd.as_bytes())
+        let args = [
+            "list-panes".to_string(),
+            "-s".to_string(),
+            "-t".to_string(),
+            self.session_id.clone(),
+            "-F".to_string(),
+            PANE_DEAD_FORMAT.to_string(),
+        ];
+        let running = self.running.clone();
+        let control_client = self.control_client.clone();
+        let version = self.version;
+
+        spawn(move || {
+            let mut dead_pids = HashSet::new();
+            while running.load(Ordering::Relaxed) {
+                sleep(POLL_INTERVAL);
+                let output = match control_client.run(&args) {
+                    Ok(output) => output,
+                    Err(e) => {
+                        error!("Error polling for dead panes: {}", e);
+                        continue;
+                    }
+                };
+                let mut still_dead = HashSet::new();
+                for line in String::from_utf8_lossy(&output.stdout).lines() {
+                    if let Some((pid, exit_code)) = parse_pane_dead(line, version) {
+                        if !dead_pids.contains(&pid)
+                            && sender.send(TmuxEvent::PaneDead { pid, exit_code }).is_err()
+                        {
+                            return;
+                        }
+                        still_dead.insert(pid);
+                    }
+                }
+                dead_pids = still_dead;
+            }
+        });
     }
 
     pub fn kill(&mut self) -> std::io::Result<ExitStatus> {
@@ -55,25 +277,104 @@ impl TmuxDaemon {
             self.session_id
         );
         self.running.store(false, Ordering::Relaxed);
-        self.process.kill()?;
-        self.process.wait() // make sure stdin is closed
+        let mut process = self.process.lock().unwrap();
+        process.kill()?;
+        process.wait() // make sure stdin is closed
+    }
+
+    pub fn listen(&mut self, sender: Sender<TmuxEvent>) -> Result<(), Box<dyn Error>> {
+        let stdout = self.stdout.lock().unwrap().take().unwrap();
+        let reader = self.start_reading(stdout, sender.clone())?;
+        if !self.version.supports_subscriptions() {
+            self.poll_pane_dead(sender.clone());
+        }
+        self.watch(reader, sender);
+        Ok(())
     }
 
-    pub fn listen_for_dead_panes(&mut self, sender: Sender<i32>) -> Result<(), Box<dyn Error>> {
-        let mut buf_reader = BufReader::new(self.stdout.take().unwrap());
+    /*
+     * Reads the output of the control mode client on a separate thread,
+     * returning once the client is attached and subscribed
+     */
+    fn start_reading(
+        &self,
+        stdout: ChildStdout,
+        sender: Sender<TmuxEvent>,
+    ) -> Result<JoinHandle<()>, Box<dyn Error>> {
+        let mut buf_reader = BufReader::new(stdout);
         let running = self.running.clone();
         let subscription_name = self.subscription_name.clone();
         let session_id = self.session_id.clone();
+        let control_client = self.control_client.clone();
+        let version = self.version;
+        let (attached_sender, attached) = channel();
 
-        spawn(move || {
+        let reader = spawn(move || {
+            let mut pending_output: HashMap<String, String> = HashMap::new();
+            // the command number and output of the block being read, if the
+            // block answers a command sent by this client
+            let mut block: Option<(String, Vec<String>)> = None;
             while running.load(Ordering::Relaxed) {
-                let mut buf = String::new();
-                match buf_reader.read_line(&mut buf) {
+                let mut buf = vec![];
+                match buf_reader.read_until(b'\n', &mut buf) {
+                    Ok(0) => return,
                     Ok(_) => {
-                        if let Some(pid) =
-                            parse_pane_dead_notification(buf, &subscription_name, &session_id)
+                        let line = String::from_utf8_lossy(&buf).to_string();
+                        if let Some((number, lines)) = &mut block {
+                            match parse_block_end(&line, number) {
+                                Some(error) => {
+                                    let lines = std::mem::take(lines);
+                                    control_client.answer(if error {
+                                        Err(lines.join("\n"))
+                                    } else {
+                                        Ok(lines)
+                                    });
+                                    block = None;
+                                }
+                                None => lines.push(line.trim_end_matches('\n').to_string()),
+                            }
+                        } else if is_session_changed(&line, &session_id) {
+                            attached_sender.send(()).unwrap_or(());
+                        } else if let Some((number, from_client)) = parse_block_begin(&line) {
+                            // blocks not answering this client (e.g. the attach) are skipped
+                            if from_client {
+                                block = Some((number, vec![]));
+                            }
+                        } else if is_window_close(&line) {
+                            sender.send(TmuxEvent::WindowClosed).unwrap();
+                        } else if let Some((pane_id, value)) =
+                            parse_pane_dead_notification(&line, &subscription_name, &session_id)
                         {
-                            sender.send(pid).unwrap();
+                            // the output of a dead pane is complete, its last line may lack a newline
+                            if is_pane_dead(value) {
+                                if let Some(pending) = pending_output.remove(pane_id) {
+                                    if !pending.is_empty() {
+                                        sender
+                                            .send(TmuxEvent::PaneOutput {
+                                                pane_id: pane_id.to_string(),
+                                                line: strip_ansi(&pending),
+                                            })
+                                            .unwrap();
+                                    }
+                                }
+                            }
+                            if let Some((pid, exit_code)) = parse_pane_dead(value, version) {
+                                sender.send(TmuxEvent::PaneDead { pid, exit_code }).unwrap();
+                            }
+                        } else if let Some((pane_id, output)) = parse_output_notification(&line) {
+                            let pending = pending_output.entry(pane_id.clone()).or_default();
+                            for line in split_output_lines(pending, &output) {
+                                sender
+                                    .send(TmuxEvent::PaneOutput {
+                                        pane_id: pane_id.clone(),
+                                        line,
+                                    })
+                                    .unwrap();
+                            }
+                            // only partial lines are kept, so panes closed while alive leave nothing behind
+                            if pending_output.get(&pane_id).is_some_and(|p| p.is_empty()) {
+                                pending_output.remove(&pane_id);
+                            }
                         }
                     }
                     _ => return,
@@ -81,26 +382,357 @@ impl TmuxDaemon {
             }
         });
 
-        self.subscribe_to_pane_dead_notifications()?;
-        Ok(())
+        /*
+         * Until the client is attached to its session, tmux runs the commands
+         * it sends against another client, so the subscription would be
+         * created for (or refused by) the wrong client
+         */
+        attached.recv_timeout(COMMAND_TIMEOUT).map_err(|_| {
+            format!(
+                "tmux control mode client did not attach to session {}",
+                self.session_id
+            )
+        })?;
+        if self.version.supports_subscriptions() {
+            self.subscribe_to_pane_dead_notifications()?;
+        }
+        Ok(reader)
+    }
+
+    /*
+     * Starts the control mode client again whenever it exits (e.g. it was
+     * killed, or tmux dropped it) until the daemon is killed. Exits missed
+     * meanwhile are picked up by the controller reconciling with tmux.
+     */
+    fn watch(&self, reader: JoinHandle<()>, sender: Sender<TmuxEvent>) {
+        let daemon = self.clone();
+        spawn(move || {
+            let mut reader = reader;
+            loop {
+                reader.join().unwrap_or(());
+                loop {
+                    if !daemon.running.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    error!(
+                        "tmux control mode client (Session: {}) exited, restarting",
+                        daemon.session_id
+                    );
+                    sleep(RESTART_DELAY);
+                    match daemon.restart(sender.clone()) {
+                        Ok(new_reader) => {
+                            reader = new_reader;
+                            break;
+                        }
+                        Err(e) => error!(
+                            "Error restarting tmux control mode client (Session: {}): {}",
+                            daemon.session_id, e
+                        ),
+                    }
+                }
+            }
+        });
+    }
+
+    fn restart(&self, sender: Sender<TmuxEvent>) -> Result<JoinHandle<()>, Box<dyn Error>> {
+        let mut process = tmux::control_mode(&self.server, &self.session_id)?;
+        let stdin = process.stdin.take().unwrap();
+        let stdout = process.stdout.take().unwrap();
+        // the old client has exited (or was killed), so waiting does not block
+        let mut exited = std::mem::replace(&mut *self.process.lock().unwrap(), process);
+        exited.wait().unwrap_or_default();
+        self.control_client.reconnect(stdin);
+        let result = self.start_reading(stdout, sender);
+        if result.is_err() {
+            // ends the reader, so the next attempt starts from scratch
+            self.process.lock().unwrap().kill().unwrap_or(());
+        }
+        result
+    }
+}
+
+/*
+ * %session-changed <session id> <session name>, sent once the client is attached
+ */
+fn is_session_changed(line: &str, session_id: &str) -> bool {
+    line.strip_prefix("%session-changed ")
+        .and_then(|rest| rest.split_whitespace().next())
+        == Some(session_id)
+}
+
+/*
+ * %window-close <window id> for windows of the attached session,
+ * %unlinked-window-close <window id> for those of other sessions
+ */
+fn is_window_close(line: &str) -> bool {
+    line.starts_with("%window-close ") || line.starts_with("%unlinked-window-close ")
+}
+
+/*
+ * %begin <time> <command number> <flags>
+ * flags is 1 for the commands sent by this client
+ */
+fn parse_block_begin(line: &str) -> Option<(String, bool)> {
+    let mut fields = line.strip_prefix("%begin ")?.split_whitespace();
+    let number = fields.nth(1)?;
+    Some((number.to_string(), fields.next() == Some("1")))
+}
+
+/*
+ * %end <time> <command number> <flags>, or %error for a failed command.
+ * Returns whether the command failed, if the line ends the block of the command.
+ */
+fn parse_block_end(line: &str, number: &str) -> Option<bool> {
+    let (error, rest) = match line.strip_prefix("%end ") {
+        Some(rest) => (false, rest),
+        None => (true, line.strip_prefix("%error ")?),
+    };
+    (rest.split_whitespace().nth(1)? == number).then_some(error)
+}
+
+/*
+ * Quotes an argument for the tmux command parser. Single quoted
+ * strings are taken literally, so only the quote itself needs care.
+ */
+fn quote_argument(arg: &str) -> String {
+    // tmux expands $VARIABLE outside of single quotes
+    let is_plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "%@:.,/=+-_".contains(c));
+    if is_plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
     }
 }
 
-fn parse_pane_dead_notification(
-    line: String,
+/*
+ * %subscription-changed <name> <session> <window> <index> <pane> ... : <value>,
+ * returning the pane and the value (see PANE_DEAD_FORMAT)
+ */
+fn parse_pane_dead_notification<'a>(
+    line: &'a str,
     subscription_name: &str,
     session_id: &str,
-) -> Option<i32> {
+) -> Option<(&'a str, &'a str)> {
     trace!("Control mode line (Session: {}): {}", session_id, line);
-    if line.starts_with(&format!("%subscription-changed {}", subscription_name)) {
-        let ss: Vec<&str> = line.split(' ').collect();
-        if ss[ss.len() - 2] == "1" {
-            return ss[ss.len() - 1].trim().parse().ok();
+    let (fields, value) = line
+        .strip_prefix("%subscription-changed ")?
+        .split_once(" : ")?;
+    let mut fields = fields.split_whitespace();
+    if fields.next() != Some(subscription_name) {
+        return None;
+    }
+    Some((fields.nth(3)?, value.trim_end_matches('\n')))
+}
+
+fn is_pane_dead(value: &str) -> bool {
+    value.split_whitespace().next() == Some("1")
+}
+
+/*
+ * <pane_dead> <pane_pid> <pane_dead_status>/<pane_dead_signal>, see PANE_DEAD_FORMAT.
+ * pane_dead_status is empty if the process was killed by a signal. Both are
+ * empty when tmux has seen the pane close before reaping its process, then
+ * the exit is reported by the notification (or poll) following once they are
+ * set. Before tmux 3.3 a signal can not be told apart from not being reaped
+ * yet, so the exit is reported without an exit code right away.
+ */
+pub fn parse_pane_dead(value: &str, version: TmuxVersion) -> Option<(i32, Option<i32>)> {
+    let (pid, status, signal) = parse_pane_dead_values(value)?;
+    if status.is_empty() && signal.is_empty() && version.supports_dead_signal() {
+        return None;
+    }
+    Some((pid, status.parse().ok()))
+}
+
+/*
+ * Like parse_pane_dead, but a pane whose process is not reaped yet is dead
+ * all the same, with an exit code which is not known (yet)
+ */
+pub fn parse_listed_pane_dead(value: &str) -> Option<(i32, Option<i32>)> {
+    let (pid, status, _signal) = parse_pane_dead_values(value)?;
+    Some((pid, status.parse().ok()))
+}
+
+fn parse_pane_dead_values(value: &str) -> Option<(i32, &str, &str)> {
+    if !is_pane_dead(value) {
+        return None;
+    }
+    let mut values = value.split_whitespace().skip(1);
+    let pid = values.next()?.parse().ok()?;
+    let (status, signal) = values.next()?.split_once('/')?;
+    Some((pid, status, signal))
+}
+
+/*
+ * %output %<pane id> <value>
+ */
+fn parse_output_notification(line: &str) -> Option<(String, String)> {
+    let rest = line.strip_prefix("%output ")?;
+    let (pane_id, value) = rest.split_once(' ')?;
+    Some((
+        pane_id.to_string(),
+        unescape_output(value.trim_end_matches('\n')),
+    ))
+}
+
+/*
+ * tmux escapes characters less than ASCII 32 and backslashes in
+ * control mode output as octal, e.g. \015\012 for \r\n
+ */
+fn unescape_output(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let is_octal_escape = bytes[i] == b'\\'
+            && i + 3 < bytes.len()
+            && bytes[i + 1..i + 4]
+                .iter()
+                .all(|b| (b'0'..=b'7').contains(b));
+        if is_octal_escape {
+            let octal = bytes[i + 1..i + 4]
+                .iter()
+                .fold(0u32, |acc, b| acc * 8 + (b - b'0') as u32);
+            unescaped.push(octal as u8);
+            i += 4;
+        } else {
+            unescaped.push(bytes[i]);
+            i += 1;
         }
     }
-    None
+    String::from_utf8_lossy(&unescaped).to_string()
+}
+
+/*
+ * Appends pane output to any pending partial line, returning
+ * every completed line stripped of escape sequences
+ */
+fn split_output_lines(pending: &mut String, output: &str) -> Vec<String> {
+    pending.push_str(output);
+    let mut lines = vec![];
+    while let Some(idx) = pending.find('\n') {
+        let line: String = pending.drain(..=idx).collect();
+        lines.push(strip_ansi(&line));
+    }
+    if pending.len() > MAX_PENDING_OUTPUT {
+        lines.push(strip_ansi(pending));
+        pending.clear();
+    }
+    lines
 }
 
 fn clean(s: &str) -> String {
     s.replace("$", "")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pane_dead_notifications() {
+        let parse = |line| parse_pane_dead_notification(line, "sub_0", "$0");
+        assert_eq!(
+            parse("%subscription-changed sub_0 $0 @0 0 %5 : 1 3184 2/\n"),
+            Some(("%5", "1 3184 2/"))
+        );
+        assert_eq!(
+            parse("%subscription-changed sub_0 $0 @0 0 %5 : 0 3184 /\n"),
+            Some(("%5", "0 3184 /"))
+        );
+        assert_eq!(
+            parse("%subscription-changed sub_01 $0 @0 0 %5 : 1 3184 0\n"),
+            None
+        );
+        assert_eq!(parse("%output %5 : 1 3184 0\n"), None);
+    }
+
+    #[test]
+    fn parses_pane_dead() {
+        let version = TmuxVersion { major: 3, minor: 3 };
+        assert_eq!(parse_pane_dead("1 3184 2/", version), Some((3184, Some(2))));
+        assert_eq!(parse_pane_dead("1 3184 /9", version), Some((3184, None)));
+        // not reaped yet
+        assert_eq!(parse_pane_dead("1 3184 /", version), None);
+        assert_eq!(parse_pane_dead("0 3184 /", version), None);
+        assert_eq!(parse_listed_pane_dead("1 3184 /"), Some((3184, None)));
+        assert_eq!(parse_listed_pane_dead("1 3184 2/"), Some((3184, Some(2))));
+        assert_eq!(parse_listed_pane_dead("0 3184 /"), None);
+
+        let version = TmuxVersion { major: 3, minor: 2 };
+        assert_eq!(parse_pane_dead("1 3184 2/", version), Some((3184, Some(2))));
+        assert_eq!(parse_pane_dead("1 3184 /", version), Some((3184, None)));
+    }
+
+    #[test]
+    fn matches_session_changed() {
+        assert!(is_session_changed("%session-changed $1 proctmux\n", "$1"));
+        assert!(!is_session_changed("%session-changed $10 other\n", "$1"));
+        assert!(!is_session_changed(
+            "%client-session-changed client-1 $1 proctmux\n",
+            "$1"
+        ));
+    }
+
+    #[test]
+    fn matches_window_close() {
+        assert!(is_window_close("%window-close @3\n"));
+        assert!(is_window_close("%unlinked-window-close @3\n"));
+        assert!(!is_window_close("%window-add @3\n"));
+    }
+
+    #[test]
+    fn matches_command_blocks() {
+        assert_eq!(
+            parse_block_begin("%begin 1792365808 263 1\n"),
+            Some(("263".to_string(), true))
+        );
+        assert_eq!(
+            parse_block_begin("%begin 1792365808 265 0\n"),
+            Some(("265".to_string(), false))
+        );
+        assert_eq!(
+            parse_block_end("%end 1792365808 263 1\n", "263"),
+            Some(false)
+        );
+        assert_eq!(
+            parse_block_end("%error 1792365808 263 1\n", "263"),
+            Some(true)
+        );
+        assert_eq!(parse_block_end("%end 1792365808 264 1\n", "263"), None);
+        assert_eq!(parse_block_end("can't find pane: %999\n", "263"), None);
+    }
+
+    #[test]
+    fn quotes_command_arguments() {
+        assert_eq!(quote_argument("%12"), "%12");
+        assert_eq!(quote_argument("$1:3"), "'$1:3'");
+        assert_eq!(quote_argument("$HOME-proxy"), "'$HOME-proxy'");
+        assert_eq!(quote_argument("#{pane_id}"), "'#{pane_id}'");
+        assert_eq!(quote_argument("it's ; ok"), "'it'\\''s ; ok'");
+        assert_eq!(quote_argument(""), "''");
+    }
+
+    #[test]
+    fn parses_output_notifications() {
+        assert_eq!(
+            parse_output_notification("%output %12 error\\134n\\015\\012\n"),
+            Some(("%12".to_string(), "error\\n\r\n".to_string()))
+        );
+        assert_eq!(parse_output_notification("%begin 1 2 0\n"), None);
+    }
+
+    #[test]
+    fn splits_output_into_complete_lines() {
+        let mut pending = String::new();
+        assert!(split_output_lines(&mut pending, "compiling").is_empty());
+        assert_eq!(
+            split_output_lines(&mut pending, "...\r\n\x1b[31merror\x1b[0m\r\nwarn"),
+            vec!["compiling...".to_string(), "error".to_string()]
+        );
+        assert_eq!(pending, "warn".to_string());
+    }
+}
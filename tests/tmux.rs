@@ -0,0 +1,379 @@
+/*
+    Integration tests running proctmux in a private tmux server, driving it
+    with send-keys and checking the panes and process list tmux shows.
+    Skipped when tmux is not installed.
+*/
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+static SERVER_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/*
+ * A tmux server with its socket in a temporary directory, running proctmux
+ * in the first pane of its only session. The server is killed when dropped.
+ */
+struct TestServer {
+    socket_name: String,
+    tmp_dir: PathBuf,
+    sidebar_pane: String,
+}
+
+impl TestServer {
+    fn start(fixture: &str) -> Option<Self> {
+        let config = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(fixture);
+        Self::start_with_config(|_| config)
+    }
+
+    /*
+     * Like start, with general.control_socket set to a socket in the temporary directory
+     */
+    fn start_with_control_socket(fixture: &str) -> Option<(Self, PathBuf)> {
+        let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(fixture);
+        let mut control_socket = PathBuf::new();
+        let server = Self::start_with_config(|tmp_dir| {
+            control_socket = tmp_dir.join("control.sock");
+            let config = fs::read_to_string(&fixture).unwrap().replace(
+                "general:\n",
+                &format!(
+                    "general:\n  control_socket: '{}'\n",
+                    control_socket.display()
+                ),
+            );
+            let path = tmp_dir.join("proctmux.yaml");
+            fs::write(&path, config).unwrap();
+            path
+        })?;
+        Some((server, control_socket))
+    }
+
+    fn start_with_config<F: FnOnce(&PathBuf) -> PathBuf>(config: F) -> Option<Self> {
+        if Command::new("tmux").arg("-V").output().is_err() {
+            eprintln!("tmux is not installed, skipping");
+            return None;
+        }
+        let tmp_dir = env::temp_dir().join(format!(
+            "proctmux-test-{}-{}",
+            process::id(),
+            SERVER_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&tmp_dir).unwrap();
+        let config = config(&tmp_dir);
+        let mut server = TestServer {
+            socket_name: "proctmux".to_string(),
+            tmp_dir,
+            sidebar_pane: String::new(),
+        };
+        let output = server.tmux(&[
+            "new-session",
+            "-d",
+            "-s",
+            "main",
+            "-x",
+            "120",
+            "-y",
+            "30",
+            "-P",
+            "-F",
+            "#{pane_id}",
+            env!("CARGO_BIN_EXE_proctmux"),
+            "-L",
+            &server.socket_name.clone(),
+            config.to_str().unwrap(),
+        ]);
+        assert!(output.status.success(), "{:?}", output);
+        server.sidebar_pane = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Some(server)
+    }
+
+    fn tmux(&self, args: &[&str]) -> Output {
+        Command::new("tmux")
+            .env("TMUX_TMPDIR", &self.tmp_dir)
+            .env_remove("TMUX")
+            .arg("-L")
+            .arg(&self.socket_name)
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    fn stdout(&self, args: &[&str]) -> String {
+        String::from_utf8_lossy(&self.tmux(args).stdout).to_string()
+    }
+
+    fn send_keys(&self, keys: &str) {
+        for key in keys.chars() {
+            self.tmux(&["send-keys", "-t", &self.sidebar_pane, &key.to_string()]);
+            // give proctmux a moment, so keys are not handled before the previous one took effect
+            sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn capture(&self, pane_id: &str) -> String {
+        self.stdout(&["capture-pane", "-p", "-t", pane_id])
+    }
+
+    fn sidebar(&self) -> String {
+        self.capture(&self.sidebar_pane)
+    }
+
+    /*
+     * The line of the process list showing the process
+     */
+    fn process_line(&self, label: &str) -> String {
+        self.sidebar()
+            .lines()
+            .find(|line| line.split_whitespace().nth(1) == Some(label))
+            .unwrap_or("")
+            .to_string()
+    }
+
+    /*
+     * Panes in the window of the sidebar, other than the sidebar
+     */
+    fn process_panes(&self) -> Vec<String> {
+        self.stdout(&["list-panes", "-t", &self.sidebar_pane, "-F", "#{pane_id}"])
+            .lines()
+            .filter(|pane_id| *pane_id != self.sidebar_pane)
+            .map(|pane_id| pane_id.to_string())
+            .collect()
+    }
+
+    fn sessions(&self) -> Vec<String> {
+        self.stdout(&["list-sessions", "-F", "#{session_name}"])
+            .lines()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn wait_for<F: Fn(&Self) -> bool>(&self, description: &str, condition: F) {
+        let start = Instant::now();
+        while !condition(self) {
+            if start.elapsed() > TIMEOUT {
+                panic!(
+                    "Timed out waiting for {}, sidebar:\n{}",
+                    description,
+                    self.sidebar()
+                );
+            }
+            sleep(Duration::from_millis(50));
+        }
+    }
+
+    /*
+     * Sends a command to the control API once proctmux listens, returning its answer
+     */
+    fn control(&self, control_socket: &Path, command: &str) -> String {
+        self.wait_for("the control socket", |_| control_socket.exists());
+        let mut stream = UnixStream::connect(control_socket).unwrap();
+        writeln!(stream, "{}", command).unwrap();
+        let mut answer = String::new();
+        BufReader::new(stream).read_line(&mut answer).unwrap();
+        answer.trim().to_string()
+    }
+
+    fn wait_for_startup(&self) {
+        self.wait_for("the process list", |s| {
+            s.process_line("server").contains('▲')
+        });
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.tmux(&["kill-server"]);
+        fs::remove_dir_all(&self.tmp_dir).unwrap_or(());
+    }
+}
+
+#[test]
+fn shows_the_selected_process_beside_the_sidebar() {
+    let Some(server) = TestServer::start("proctmux.yaml") else {
+        return;
+    };
+    server.wait_for_startup();
+    assert!(server.process_panes().is_empty());
+
+    server.send_keys("j");
+    server.wait_for("the server pane to be joined", |s| {
+        s.process_panes().len() == 1
+    });
+    let pane_id = server.process_panes()[0].clone();
+    server.wait_for("the server output", |s| {
+        s.capture(&pane_id).contains("server started")
+    });
+
+    // failing has not been started, so it has no pane to show
+    server.send_keys("j");
+    server.wait_for("the server pane to be moved away", |s| {
+        s.process_panes().is_empty()
+    });
+
+    server.send_keys("k");
+    server.wait_for("the server pane to be joined again", |s| {
+        s.process_panes() == vec![pane_id.clone()]
+    });
+}
+
+#[test]
+fn detects_processes_exiting() {
+    let Some(server) = TestServer::start("proctmux.yaml") else {
+        return;
+    };
+    server.wait_for_startup();
+    assert!(server.process_line("failing").contains('▼'));
+
+    server.send_keys("jjs");
+    server.wait_for("the failing process to start", |s| {
+        s.process_line("failing").contains('▲')
+    });
+    server.wait_for("the exit code of the failing process", |s| {
+        // tmux may leave the process of a dead pane unreaped until another of
+        // its children exits, proctmux then picks up the exit code later
+        s.tmux(&["run-shell", "true"]);
+        let line = s.process_line("failing");
+        line.contains('▼') && line.contains("exit 3")
+    });
+    assert!(server.process_line("server").contains('▲'));
+}
+
+#[test]
+fn detects_processes_exiting_on_startup() {
+    let Some(server) = TestServer::start("proctmux.yaml") else {
+        return;
+    };
+    server.wait_for_startup();
+    server.wait_for("the crashing process to halt", |s| {
+        s.process_line("crashing").contains('▼')
+    });
+}
+
+#[test]
+fn starts_processes_with_multi_line_commands() {
+    let Some(server) = TestServer::start("proctmux.yaml") else {
+        return;
+    };
+    server.wait_for_startup();
+
+    server.send_keys("jjjjjs");
+    server.wait_for("script to start", |s| {
+        s.process_line("script").contains('▲')
+    });
+    server.wait_for("the script output", |s| {
+        s.process_panes()
+            .iter()
+            .any(|pane_id| s.capture(pane_id).contains("script line two"))
+    });
+    // commands sent afterwards are still answered
+    server.send_keys("x");
+    server.wait_for("script to stop", |s| s.process_line("script").contains('▼'));
+}
+
+#[test]
+fn starts_and_stops_processes_with_keys() {
+    let Some(server) = TestServer::start("proctmux.yaml") else {
+        return;
+    };
+    server.wait_for_startup();
+    assert!(server.process_line("idle").contains('▼'));
+
+    server.send_keys("jjjs");
+    server.wait_for("idle to start", |s| s.process_line("idle").contains('▲'));
+    server.wait_for("the idle output", |s| {
+        s.process_panes()
+            .iter()
+            .any(|pane_id| s.capture(pane_id).contains("idle started"))
+    });
+
+    server.send_keys("x");
+    server.wait_for("idle to stop", |s| s.process_line("idle").contains('▼'));
+}
+
+#[test]
+fn control_api_acts_on_every_process_whatever_the_filter() {
+    let Some((server, control_socket)) = TestServer::start_with_control_socket("proctmux.yaml")
+    else {
+        return;
+    };
+    server.wait_for_startup();
+
+    // bulk actions are scoped to the filter by default, which hides the server
+    server.send_keys("/idle");
+    server.tmux(&["send-keys", "-t", &server.sidebar_pane, "Enter"]);
+    server.wait_for("the filter to hide the server", |s| {
+        s.process_line("server").is_empty()
+    });
+
+    assert_eq!(server.control(&control_socket, "stop_all"), "ok");
+    assert_eq!(
+        server.control(&control_socket, "stop"),
+        "error: unknown command: stop"
+    );
+
+    // clear the filter
+    server.send_keys("//");
+    server.wait_for("the server to stop", |s| {
+        s.process_line("server").contains('▼')
+    });
+}
+
+#[test]
+fn detects_panes_killed_outside_of_proctmux() {
+    let Some(server) = TestServer::start("proctmux.yaml") else {
+        return;
+    };
+    server.wait_for_startup();
+    let server_pane = server
+        .stdout(&[
+            "list-panes",
+            "-s",
+            "-t",
+            "proctmux-test",
+            "-F",
+            "#{pane_id} #{pane_start_command}",
+        ])
+        .lines()
+        .find(|line| line.contains("server started"))
+        .and_then(|line| line.split_whitespace().next())
+        .unwrap()
+        .to_string();
+
+    server.tmux(&["kill-pane", "-t", &server_pane]);
+    server.wait_for("the server process to halt", |s| {
+        s.process_line("server").contains('▼')
+    });
+
+    server.send_keys("js");
+    server.wait_for("the server process to start again", |s| {
+        s.process_line("server").contains('▲')
+    });
+    server.wait_for("the new server pane to be joined", |s| {
+        s.process_panes()
+            .iter()
+            .any(|pane_id| s.capture(pane_id).contains("server started"))
+    });
+}
+
+#[test]
+fn quitting_stops_processes_and_cleans_up() {
+    let Some(server) = TestServer::start("proctmux.yaml") else {
+        return;
+    };
+    server.wait_for_startup();
+    assert!(server.sessions().contains(&"proctmux-test".to_string()));
+
+    server.send_keys("q");
+    server.wait_for("proctmux to exit", |s| s.sessions().is_empty());
+}